@@ -0,0 +1,72 @@
+//! Perturbed shading normals, which make surfaces look bumpy without changing their
+//! geometry.
+
+use crate::common::Vec3;
+use crate::hittable::HitRecord;
+use crate::texture::{ImageTexture, TextureMap};
+
+/// Step in `u` and `v` for the difference quotients of bump heights.
+const DELTA: f64 = 1e-4;
+
+/// A perturbation of a material's shading normal.
+#[derive(Clone)]
+pub enum Bump {
+    /// Bump mapping: the surface is shaded as if displaced along its normal by a height
+    /// in world units, given by a texture map.
+    Height(TextureMap),
+    /// Normal mapping: an image of tangent-space normals, with red along `u`, green along
+    /// `v` (the OpenGL convention) and blue along the normal, each mapped from `[-1, 1]`
+    /// to `[0, 1]`. `strength` scales the tilt, 0 leaving the normal unchanged.
+    NormalMap { texture: ImageTexture, strength: f64 },
+}
+
+impl Bump {
+    /// The perturbed unit normal at `hit`, facing the same side as `hit.normal`.
+    pub fn normal(&self, hit: &HitRecord) -> Vec3 {
+        let n = hit.outward_normal();
+        let (dpdu, dpdv) = hit.derivatives();
+        let perturbed = match self {
+            Bump::Height(map) => {
+                let (u, v) = hit.uv;
+                let height = map.value(&hit.point, hit.uv);
+                let slope = |dp: &Vec3, uv: (f64, f64)| {
+                    (map.value(&hit.point.add(&dp.scale(DELTA)), uv) - height) / DELTA
+                };
+                let dhdu = slope(&dpdu, (u + DELTA, v));
+                let dhdv = slope(&dpdv, (u, v + DELTA));
+
+                // normal of the displaced surface, ignoring the change of the normal itself
+                let normal = dpdu.add(&n.scale(dhdu)).cross(&dpdv.add(&n.scale(dhdv)));
+                if normal.dot(&n) < 0.0 {
+                    normal.scale(-1.0)
+                } else {
+                    normal
+                }
+            }
+            Bump::NormalMap { texture, strength } => {
+                let c = texture.color(hit.uv);
+                let (x, y, z) = ((2.0 * c.r - 1.0) * strength, (2.0 * c.g - 1.0) * strength, 2.0 * c.b - 1.0);
+
+                // orthonormal tangent frame following the UV directions
+                let tangent = dpdu.sub(&n.scale(n.dot(&dpdu))).normalize();
+                let bitangent = dpdv
+                    .sub(&n.scale(n.dot(&dpdv)))
+                    .sub(&tangent.scale(tangent.dot(&dpdv)))
+                    .normalize();
+                tangent.scale(x).add(&bitangent.scale(y)).add(&n.scale(z))
+            }
+        };
+
+        // degenerate tangent frames, e.g. at the poles of a sphere, leave the normal as is
+        let length = perturbed.length();
+        if length.is_nan() || length < 1e-12 {
+            return hit.normal;
+        }
+        let perturbed = perturbed.scale(1.0 / length);
+        if hit.front_face {
+            perturbed
+        } else {
+            perturbed.scale(-1.0)
+        }
+    }
+}
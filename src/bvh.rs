@@ -0,0 +1,454 @@
+//! Bounding volume hierarchy for accelerating ray-object intersection.
+//!
+//! The hierarchy is built top-down with the surface area heuristic (SAH), evaluated over
+//! a fixed number of centroid bins per axis, and stored as a flat array of nodes.
+
+use crate::common::Vec3;
+use crate::hittable::HitRecord;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Number of centroid bins evaluated per axis when searching for a split.
+const SAH_BINS: usize = 12;
+/// Relative cost of traversing a node compared to intersecting a primitive.
+const TRAVERSAL_COST: f64 = 1.0;
+/// Leaves never hold more primitives than this, even if splitting looks expensive.
+const MAX_LEAF_SIZE: usize = 8;
+
+/// An axis-aligned bounding box.
+#[derive(Clone, Copy)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Aabb { min, max }
+    }
+
+    /// The smallest box containing all `points`.
+    pub fn from_points<'a>(points: impl IntoIterator<Item = &'a Vec3>) -> Self {
+        points
+            .into_iter()
+            .fold(Aabb::empty(), |bounds, point| bounds.union(&Aabb::new(*point, *point)))
+    }
+
+    /// A box containing nothing, the identity of [`Aabb::union`].
+    pub fn empty() -> Self {
+        Aabb::new(
+            Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            Vec3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        )
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb::new(
+            Vec3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            Vec3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        )
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        self.min.add(&self.max).scale(0.5)
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let d = self.max.sub(&self.min);
+        if d.x < 0.0 || d.y < 0.0 || d.z < 0.0 {
+            return 0.0;
+        }
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    /// Slab test against a ray given by its origin and the reciprocal of its direction.
+    pub fn hit(&self, origin: &Vec3, inv_direction: &Vec3, t_min: f64, t_max: f64) -> bool {
+        let mut t0 = t_min;
+        let mut t1 = t_max;
+        for axis in 0..3 {
+            let (o, inv, lo, hi) = match axis {
+                0 => (origin.x, inv_direction.x, self.min.x, self.max.x),
+                1 => (origin.y, inv_direction.y, self.min.y, self.max.y),
+                _ => (origin.z, inv_direction.z, self.min.z, self.max.z),
+            };
+            let near = (lo - o) * inv;
+            let far = (hi - o) * inv;
+            if near.is_nan() || far.is_nan() {
+                // the ray runs inside a boundary plane of the slab, which counts as inside
+                continue;
+            }
+            t0 = t0.max(near.min(far));
+            t1 = t1.min(near.max(far));
+            if t0 > t1 {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+fn axis_value(v: &Vec3, axis: usize) -> f64 {
+    match axis {
+        0 => v.x,
+        1 => v.y,
+        _ => v.z,
+    }
+}
+
+/// Size and build time of a [`Bvh`].
+#[derive(Clone, Copy, Default)]
+pub struct BvhStats {
+    pub primitives: usize,
+    pub nodes: usize,
+    pub leaves: usize,
+    pub max_depth: usize,
+    pub build_time: Duration,
+}
+
+impl fmt::Display for BvhStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} nodes ({} leaves, max depth {}) over {} primitives, built in {:.2?}",
+            self.nodes, self.leaves, self.max_depth, self.primitives, self.build_time
+        )
+    }
+}
+
+#[derive(Clone, Copy)]
+enum NodeKind {
+    /// Primitives `indices[first..first + count]`.
+    Leaf { first: usize, count: usize },
+    /// The left child directly follows its parent, the right child is at `right`.
+    Interior { right: usize, axis: usize },
+}
+
+#[derive(Clone, Copy)]
+struct Node {
+    bounds: Aabb,
+    kind: NodeKind,
+}
+
+/// A bounding volume hierarchy over primitives identified by their index.
+///
+/// The hierarchy only stores bounding boxes; traversal calls back with the index of each
+/// candidate primitive so the caller can run the exact intersection test.
+pub struct Bvh {
+    nodes: Vec<Node>,
+    indices: Vec<usize>,
+    stats: BvhStats,
+}
+
+impl Bvh {
+    /// Build a hierarchy over primitives with the given bounding boxes.
+    pub fn build(bounds: &[Aabb]) -> Self {
+        let start = Instant::now();
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(2 * bounds.len()),
+            indices: (0..bounds.len()).collect(),
+            stats: BvhStats {
+                primitives: bounds.len(),
+                ..BvhStats::default()
+            },
+        };
+        if !bounds.is_empty() {
+            let centroids: Vec<Vec3> = bounds.iter().map(Aabb::centroid).collect();
+            bvh.build_node(bounds, &centroids, 0, bounds.len(), 1);
+        }
+        bvh.stats.nodes = bvh.nodes.len();
+        bvh.stats.build_time = start.elapsed();
+        bvh
+    }
+
+    pub fn stats(&self) -> &BvhStats {
+        &self.stats
+    }
+
+    /// Replace every primitive index `i` with `mapping[i]`, e.g. to refer to a subset of
+    /// a larger list.
+    pub fn remap(&mut self, mapping: &[usize]) {
+        for index in &mut self.indices {
+            *index = mapping[*index];
+        }
+    }
+
+    /// Build the node for `indices[first..end]`, returning its index.
+    fn build_node(&mut self, bounds: &[Aabb], centroids: &[Vec3], first: usize, end: usize, depth: usize) -> usize {
+        let node_index = self.nodes.len();
+        let node_bounds = self.indices[first..end]
+            .iter()
+            .fold(Aabb::empty(), |acc, &i| acc.union(&bounds[i]));
+        self.nodes.push(Node {
+            bounds: node_bounds,
+            kind: NodeKind::Leaf { first, count: end - first },
+        });
+        self.stats.max_depth = self.stats.max_depth.max(depth);
+
+        let count = end - first;
+        let split = if count > 1 {
+            self.find_split(bounds, centroids, first, end, &node_bounds)
+        } else {
+            None
+        };
+        let Some((axis, position)) = split else {
+            self.stats.leaves += 1;
+            return node_index;
+        };
+
+        // partition the primitives around the split plane
+        let mut mid = first;
+        for i in first..end {
+            if axis_value(&centroids[self.indices[i]], axis) < position {
+                self.indices.swap(i, mid);
+                mid += 1;
+            }
+        }
+        if mid == first || mid == end {
+            // all centroids on one side, fall back to splitting the list in half
+            mid = first + count / 2;
+        }
+
+        self.build_node(bounds, centroids, first, mid, depth + 1);
+        let right = self.build_node(bounds, centroids, mid, end, depth + 1);
+        self.nodes[node_index].kind = NodeKind::Interior { right, axis };
+        node_index
+    }
+
+    /// Find the cheapest split plane by the surface area heuristic.
+    ///
+    /// # Returns
+    ///
+    /// Returns the split axis and position, or `None` if a leaf is cheaper.
+    fn find_split(&self, bounds: &[Aabb], centroids: &[Vec3], first: usize, end: usize, node_bounds: &Aabb) -> Option<(usize, f64)> {
+        let count = end - first;
+        let centroid_bounds = Aabb::from_points(self.indices[first..end].iter().map(|&i| &centroids[i]));
+
+        let mut best: Option<(f64, usize, f64)> = None;
+        for axis in 0..3 {
+            let lo = axis_value(&centroid_bounds.min, axis);
+            let hi = axis_value(&centroid_bounds.max, axis);
+            if hi <= lo {
+                continue;
+            }
+
+            let mut bins = [(Aabb::empty(), 0usize); SAH_BINS];
+            let bin_of = |c: f64| (((c - lo) / (hi - lo) * SAH_BINS as f64) as usize).min(SAH_BINS - 1);
+            for &i in &self.indices[first..end] {
+                let bin = &mut bins[bin_of(axis_value(&centroids[i], axis))];
+                bin.0 = bin.0.union(&bounds[i]);
+                bin.1 += 1;
+            }
+
+            // sweep from the right to get the cost of every right-hand side
+            let mut right_costs = [0.0; SAH_BINS];
+            let mut acc = (Aabb::empty(), 0);
+            for b in (1..SAH_BINS).rev() {
+                acc = (acc.0.union(&bins[b].0), acc.1 + bins[b].1);
+                right_costs[b] = acc.0.surface_area() * acc.1 as f64;
+            }
+
+            let mut acc = (Aabb::empty(), 0);
+            for b in 0..SAH_BINS - 1 {
+                acc = (acc.0.union(&bins[b].0), acc.1 + bins[b].1);
+                let cost = acc.0.surface_area() * acc.1 as f64 + right_costs[b + 1];
+                if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                    let position = lo + (hi - lo) * (b + 1) as f64 / SAH_BINS as f64;
+                    best = Some((cost, axis, position));
+                }
+            }
+        }
+
+        let Some((cost, axis, position)) = best else {
+            // every centroid coincides; split the list in half if it is too big for a leaf
+            return (count > MAX_LEAF_SIZE).then_some((0, f64::NEG_INFINITY));
+        };
+        let split_cost = TRAVERSAL_COST + cost / node_bounds.surface_area().max(f64::MIN_POSITIVE);
+        if count <= MAX_LEAF_SIZE && split_cost >= count as f64 {
+            return None;
+        }
+        Some((axis, position))
+    }
+
+    /// Find the closest hit, calling `hit_primitive(index, t_min, t_max)` for every
+    /// primitive whose bounds the ray enters before the closest hit found so far.
+    pub fn closest_hit<'a, F>(&self, origin: &Vec3, direction: &Vec3, t_min: f64, t_max: f64, mut hit_primitive: F) -> Option<HitRecord<'a>>
+    where
+        F: FnMut(usize, f64, f64) -> Option<HitRecord<'a>>,
+    {
+        let mut closest = None;
+        self.traverse(origin, direction, t_min, t_max, false, |i, t_max| {
+            let hit = hit_primitive(i, t_min, t_max)?;
+            let t = hit.t;
+            closest = Some(hit);
+            Some(t)
+        });
+        closest
+    }
+
+    /// Check whether `hit_primitive(index, t_min, t_max)` is true for any primitive
+    /// whose bounds the ray enters, stopping at the first one.
+    pub fn any_hit<F>(&self, origin: &Vec3, direction: &Vec3, t_min: f64, t_max: f64, mut hit_primitive: F) -> bool
+    where
+        F: FnMut(usize, f64, f64) -> bool,
+    {
+        self.traverse(origin, direction, t_min, t_max, true, |i, t_max| {
+            hit_primitive(i, t_min, t_max).then_some(t_max)
+        })
+    }
+
+    /// Walk the nodes whose bounds the ray enters within `[t_min, t_max]`, near child
+    /// first, calling `visit(index, t_max)` for each primitive in them. A hit returns its
+    /// ray parameter, which then becomes the new `t_max`.
+    ///
+    /// # Returns
+    ///
+    /// Returns whether anything was hit. With `stop_at_first`, traversal ends at the
+    /// first hit.
+    fn traverse<V>(&self, origin: &Vec3, direction: &Vec3, t_min: f64, mut t_max: f64, stop_at_first: bool, mut visit: V) -> bool
+    where
+        V: FnMut(usize, f64) -> Option<f64>,
+    {
+        if self.nodes.is_empty() {
+            return false;
+        }
+        let inv_direction = Vec3::new(1.0 / direction.x, 1.0 / direction.y, 1.0 / direction.z);
+
+        let mut any = false;
+        let mut stack = Vec::with_capacity(64);
+        stack.push(0);
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if !node.bounds.hit(origin, &inv_direction, t_min, t_max) {
+                continue;
+            }
+            match node.kind {
+                NodeKind::Leaf { first, count } => {
+                    for &i in &self.indices[first..first + count] {
+                        if let Some(t) = visit(i, t_max) {
+                            if stop_at_first {
+                                return true;
+                            }
+                            any = true;
+                            t_max = t;
+                        }
+                    }
+                }
+                NodeKind::Interior { right, axis } => {
+                    // push the far child first so the near one is visited next
+                    let left = node_index + 1;
+                    if axis_value(direction, axis) < 0.0 {
+                        stack.push(left);
+                        stack.push(right);
+                    } else {
+                        stack.push(right);
+                        stack.push(left);
+                    }
+                }
+            }
+        }
+        any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{Material, Sphere};
+    use crate::hittable::Hittable;
+    use crate::mesh::Triangle;
+    use crate::sampling::Rng;
+
+    fn random_vec3(rng: &mut Rng, scale: f64) -> Vec3 {
+        let mut coordinate = || (rng.next_f64() * 2.0 - 1.0) * scale;
+        Vec3::new(coordinate(), coordinate(), coordinate())
+    }
+
+    /// A random mix of spheres and triangles, some overlapping, in a box of side 20.
+    fn random_objects(rng: &mut Rng, count: usize) -> Vec<Box<dyn Hittable>> {
+        (0..count)
+            .map(|i| -> Box<dyn Hittable> {
+                let center = random_vec3(rng, 10.0);
+                if i % 2 == 0 {
+                    Box::new(Sphere {
+                        center,
+                        radius: 0.1 + rng.next_f64(),
+                        material: Material::default(),
+                    })
+                } else {
+                    let vertices = [0, 1, 2].map(|_| center.add(&random_vec3(rng, 1.5)));
+                    Box::new(Triangle::new(vertices, Material::default()))
+                }
+            })
+            .collect()
+    }
+
+    fn linear_closest_hit<'a>(objects: &'a [Box<dyn Hittable>], origin: &Vec3, direction: &Vec3, t_min: f64, t_max: f64) -> Option<HitRecord<'a>> {
+        let mut closest = None;
+        let mut closest_t = t_max;
+        for object in objects {
+            let t = object.hit_t(origin, direction, t_min, closest_t);
+            assert_eq!(t, object.hit(origin, direction, t_min, closest_t).map(|hit| hit.t));
+            if let Some(hit) = object.hit(origin, direction, t_min, closest_t) {
+                closest_t = hit.t;
+                closest = Some(hit);
+            }
+        }
+        closest
+    }
+
+    #[test]
+    fn matches_linear_search_on_random_scenes() {
+        let mut rng = Rng::new(7);
+        for count in [1, 2, 5, 40, 300] {
+            let objects = random_objects(&mut rng, count);
+            let bounds: Vec<Aabb> = objects.iter().map(|object| object.bounding_box().unwrap()).collect();
+            let bvh = Bvh::build(&bounds);
+
+            for _ in 0..500 {
+                // aim at a random point among the objects, reached at t = 1
+                let origin = random_vec3(&mut rng, 15.0);
+                let direction = random_vec3(&mut rng, 10.0).sub(&origin);
+                let t_max = if rng.next_f64() < 0.5 { f64::INFINITY } else { rng.next_f64() * 2.0 };
+
+                let expected = linear_closest_hit(&objects, &origin, &direction, 0.001, t_max);
+                let actual = bvh.closest_hit(&origin, &direction, 0.001, t_max, |i, t_min, t_max| {
+                    objects[i].hit(&origin, &direction, t_min, t_max)
+                });
+                assert_eq!(actual.as_ref().map(|hit| hit.t), expected.as_ref().map(|hit| hit.t));
+
+                let any = bvh.any_hit(&origin, &direction, 0.001, t_max, |i, t_min, t_max| {
+                    objects[i].hit_t(&origin, &direction, t_min, t_max).is_some()
+                });
+                assert_eq!(any, expected.is_some());
+            }
+        }
+    }
+
+    #[test]
+    fn remap_translates_primitive_indices() {
+        let bounds = [
+            Aabb::new(Vec3::new(-1.0, -1.0, 4.0), Vec3::new(1.0, 1.0, 6.0)),
+            Aabb::new(Vec3::new(-1.0, -1.0, 9.0), Vec3::new(1.0, 1.0, 11.0)),
+        ];
+        let mut bvh = Bvh::build(&bounds);
+        bvh.remap(&[3, 8]);
+
+        let mut visited = Vec::new();
+        let origin = Vec3::new(0.0, 0.0, 0.0);
+        let direction = Vec3::new(0.0, 0.0, 1.0);
+        bvh.any_hit(&origin, &direction, 0.0, f64::INFINITY, |i, _, _| {
+            visited.push(i);
+            false
+        });
+        visited.sort();
+        assert_eq!(visited, [3, 8]);
+    }
+}
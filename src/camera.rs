@@ -0,0 +1,119 @@
+//! Camera used to generate primary rays.
+
+use crate::common::{config, Vec3};
+
+/// Which image axis keeps the camera's field of view when the canvas aspect ratio changes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FovAxis {
+    /// The vertical field of view is fixed, wider canvases see more to the sides.
+    Vertical,
+    /// The horizontal field of view is fixed, wider canvases see less above and below.
+    Horizontal,
+}
+
+impl FovAxis {
+    pub fn from_name(name: &str) -> Option<FovAxis> {
+        match name {
+            "vertical" => Some(FovAxis::Vertical),
+            "horizontal" => Some(FovAxis::Horizontal),
+            _ => None,
+        }
+    }
+}
+
+/// A pinhole camera at `position` looking towards `target`.
+///
+/// Primary rays are not normalized: they pass through a viewport placed at unit distance
+/// in front of the eye, so `t = 1` lies on the viewport. The viewport has the aspect
+/// ratio of the canvas, with its extent along `fov_axis` given by `viewport_size`.
+#[derive(Clone, Copy)]
+pub struct Camera {
+    pub position: Vec3,
+    pub target: Vec3,
+    /// Approximate up direction, used to orient the image. It need not be perpendicular
+    /// to the viewing direction, but must not be parallel to it.
+    pub up: Vec3,
+    /// Extent of the viewport along `fov_axis` at unit distance, `2 * tan(fov / 2)`.
+    pub viewport_size: f64,
+    pub fov_axis: FovAxis,
+}
+
+impl Camera {
+    /// Create a camera at `position` looking at `target`, with a vertical field of view
+    /// in degrees.
+    pub fn look_at(position: Vec3, target: Vec3, up: Vec3, vertical_fov: f64) -> Self {
+        let mut camera = Camera {
+            position,
+            target,
+            up,
+            viewport_size: 0.0,
+            fov_axis: FovAxis::Vertical,
+        };
+        camera.set_fov(vertical_fov);
+        camera
+    }
+
+    /// The field of view along `fov_axis` in degrees.
+    pub fn fov(&self) -> f64 {
+        2.0 * (self.viewport_size / 2.0).atan().to_degrees()
+    }
+
+    /// Set the field of view along `fov_axis` in degrees.
+    pub fn set_fov(&mut self, degrees: f64) {
+        self.viewport_size = 2.0 * (degrees.to_radians() / 2.0).tan();
+    }
+
+    /// The orthonormal camera basis as (right, up, forward) vectors.
+    pub fn basis(&self) -> (Vec3, Vec3, Vec3) {
+        let forward = self.target.sub(&self.position).normalize();
+        let right = self.up.cross(&forward).normalize();
+        let up = forward.cross(&right);
+        (right, up, forward)
+    }
+
+    /// Generate the primary ray through a point of the canvas.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The x-coordinate on the canvas in pixels from the left edge; pixel `i`
+    ///   spans `[i, i + 1)`, so `i + 0.5` is its center
+    /// * `y` - The y-coordinate on the canvas in pixels from the top edge
+    /// * `canvas_width` - The width of the canvas in pixels
+    /// * `canvas_height` - The height of the canvas in pixels
+    ///
+    /// # Returns
+    ///
+    /// Returns the ray origin and (unnormalized) direction through the viewport.
+    pub fn primary_ray(&self, x: f64, y: f64, canvas_width: u32, canvas_height: u32) -> (Vec3, Vec3) {
+        let (right, up, forward) = self.basis();
+
+        // canvas coordinates centered on the middle of the image
+        let cx = x - canvas_width as f64 / 2.0;
+        let cy = y - canvas_height as f64 / 2.0;
+
+        // pixels are square, so both axes share the scale of the fixed one
+        let fixed_extent = match self.fov_axis {
+            FovAxis::Vertical => canvas_height,
+            FovAxis::Horizontal => canvas_width,
+        } as f64;
+        let vx = cx * self.viewport_size / fixed_extent;
+        let vy = -cy * self.viewport_size / fixed_extent;
+
+        let direction = forward.add(&right.scale(vx)).add(&up.scale(vy));
+        (self.position, direction)
+    }
+}
+
+impl Default for Camera {
+    /// The original fixed camera: at the origin, looking down +Z through a
+    /// `VIEWPORT_SIZE` high viewport at `PROJECTION_PLANE_D`.
+    fn default() -> Self {
+        Camera {
+            position: Vec3::new(0.0, 0.0, 0.0),
+            target: Vec3::new(0.0, 0.0, 1.0),
+            up: Vec3::new(0.0, 1.0, 0.0),
+            viewport_size: config::VIEWPORT_SIZE / config::PROJECTION_PLANE_D,
+            fov_axis: FovAxis::Vertical,
+        }
+    }
+}
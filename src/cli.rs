@@ -0,0 +1,442 @@
+//! Command-line argument parsing for the `luxst` binary.
+
+use luxst::output::OutputFormat;
+use luxst::{Adaptive, AntiAliasing, Filter, FovAxis, SamplePattern, Stage, ToneMapOperator, ToneMapping, TransferFunction};
+use std::fmt;
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+usage: luxst render [options]
+
+options:
+    --scene <scene>      'basic' or the path of a .toml scene file (default: basic)
+    --width <pixels>     image width (default: 400)
+    --height <pixels>    image height (default: 400)
+    --out <path>         output image path, '-' for stdout (default: <stage>.<format>)
+    --format <format>    png, png16, ppm, hdr or exr (default: from the --out extension,
+                         or png; ppm when writing to stdout)
+    --stage <stage>      basic, lights, specular, shadows or reflections (default: reflections)
+    --depth <n>          maximum reflection depth (default: from the stage, 3 for
+                         reflections and 0 for the others)
+    --fov-axis <axis>    keep the camera's field of view 'vertical' or 'horizontal'
+                         when the aspect ratio changes (default: from the scene)
+    --threads <n>        number of render threads, 0 for one per core (default: 0)
+    --tone-map <op>      clamp, reinhard or aces (default: clamp)
+    --exposure <stops>   brighten (positive) or darken (negative) before tone mapping
+                         (default: 0)
+    --samples <n>        samples per pixel (default: 1, at the pixel center)
+    --pattern <pattern>  sample placement, jittered or stratified (default: stratified)
+    --filter <filter>    pixel filter, box, tent, gaussian or mitchell (default: box);
+                         needs at least 1, 4, 9 or 16 --samples respectively
+    --max-samples <n>    sample adaptively: keep adding batches of --samples samples to
+                         noisy or high-contrast pixels, up to n per pixel
+    --noise-threshold <t>
+                         refine pixels whose luminance standard error exceeds t
+                         (default: 0.01)
+    --contrast-threshold <t>
+                         refine pixels whose color differs from a neighbor's by
+                         more than t in any channel (default: 0.1)
+    --sample-map <path>  also write an image of the sample count per pixel
+    --gamma <gamma>      output encoding, 'srgb' or a gamma exponent such as 2.2
+                         (1 writes linear values, default: srgb)
+    -h, --help           print this message";
+
+/// Largest accepted canvas dimension, to catch typos before allocating the image.
+const MAX_DIMENSION: u32 = 16384;
+
+pub enum Command {
+    Render(RenderArgs),
+    Help,
+}
+
+pub struct RenderArgs {
+    pub scene: String,
+    pub width: u32,
+    pub height: u32,
+    /// Output path, `-` for stdout.
+    pub out: PathBuf,
+    pub format: OutputFormat,
+    pub stage: Stage,
+    pub depth: Option<u32>,
+    pub fov_axis: Option<FovAxis>,
+    pub threads: usize,
+    pub tone_mapping: ToneMapping,
+    pub anti_aliasing: AntiAliasing,
+    /// Where to write the sample count debug image, if anywhere.
+    pub sample_map: Option<PathBuf>,
+}
+
+pub enum CliError {
+    MissingCommand,
+    UnknownCommand(String),
+    UnknownOption(String),
+    MissingValue(String),
+    InvalidValue {
+        option: String,
+        value: String,
+        reason: String,
+    },
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::MissingCommand => write!(f, "no command given"),
+            CliError::UnknownCommand(command) => write!(f, "unknown command '{}'", command),
+            CliError::UnknownOption(option) => write!(f, "unknown option '{}'", option),
+            CliError::MissingValue(option) => write!(f, "option '{}' requires a value", option),
+            CliError::InvalidValue { option, value, reason } => {
+                write!(f, "invalid value '{}' for '{}': {}", value, option, reason)
+            }
+        }
+    }
+}
+
+/// Parse the arguments following the program name.
+pub fn parse(args: &[String]) -> Result<Command, CliError> {
+    let (command, rest) = args.split_first().ok_or(CliError::MissingCommand)?;
+    match command.as_str() {
+        "render" => parse_render(rest),
+        "-h" | "--help" | "help" => Ok(Command::Help),
+        _ => Err(CliError::UnknownCommand(command.clone())),
+    }
+}
+
+fn parse_render(args: &[String]) -> Result<Command, CliError> {
+    let mut scene = String::from("basic");
+    let mut width = luxst::common::config::CANVAS_WIDTH;
+    let mut height = luxst::common::config::CANVAS_HEIGHT;
+    let mut out = None;
+    let mut format = None;
+    let mut stage = Stage::Reflections;
+    let mut depth = None;
+    let mut fov_axis = None;
+    let mut threads = 0;
+    let mut tone_mapping = ToneMapping::default();
+    let mut anti_aliasing = AntiAliasing::default();
+    let mut adaptive: Option<Adaptive> = None;
+    let mut sample_map = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        // accept both `--flag value` and `--flag=value`
+        let (option, inline_value) = match arg.split_once('=') {
+            Some((option, value)) if option.starts_with("--") => (option, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        if option == "-h" || option == "--help" {
+            return Ok(Command::Help);
+        }
+
+        let mut value = || {
+            inline_value
+                .clone()
+                .or_else(|| args.next().cloned())
+                .ok_or_else(|| CliError::MissingValue(option.to_string()))
+        };
+
+        match option {
+            "--scene" => scene = value()?,
+            "--width" => width = parse_dimension(option, &value()?)?,
+            "--height" => height = parse_dimension(option, &value()?)?,
+            "--out" => out = Some(PathBuf::from(value()?)),
+            "--format" => {
+                let name = value()?;
+                format = Some(OutputFormat::from_name(&name).ok_or_else(|| CliError::InvalidValue {
+                    option: option.to_string(),
+                    value: name.clone(),
+                    reason: "expected one of png, png16, ppm, hdr, exr".to_string(),
+                })?);
+            }
+            "--stage" => {
+                let name = value()?;
+                stage = Stage::from_name(&name).ok_or_else(|| CliError::InvalidValue {
+                    option: option.to_string(),
+                    value: name.clone(),
+                    reason: "expected one of basic, lights, specular, shadows, reflections".to_string(),
+                })?;
+            }
+            "--depth" => {
+                let raw = value()?;
+                depth = Some(raw.parse().map_err(|_| CliError::InvalidValue {
+                    option: option.to_string(),
+                    value: raw.clone(),
+                    reason: "expected a non-negative integer".to_string(),
+                })?);
+            }
+            "--fov-axis" => {
+                let name = value()?;
+                fov_axis = Some(FovAxis::from_name(&name).ok_or_else(|| CliError::InvalidValue {
+                    option: option.to_string(),
+                    value: name.clone(),
+                    reason: "expected vertical or horizontal".to_string(),
+                })?);
+            }
+            "--threads" => {
+                let raw = value()?;
+                threads = raw.parse().map_err(|_| CliError::InvalidValue {
+                    option: option.to_string(),
+                    value: raw.clone(),
+                    reason: "expected a non-negative integer".to_string(),
+                })?;
+            }
+            "--tone-map" => {
+                let name = value()?;
+                tone_mapping.operator = ToneMapOperator::from_name(&name).ok_or_else(|| CliError::InvalidValue {
+                    option: option.to_string(),
+                    value: name.clone(),
+                    reason: "expected one of clamp, reinhard, aces".to_string(),
+                })?;
+            }
+            "--exposure" => {
+                let raw = value()?;
+                tone_mapping.exposure = raw
+                    .parse()
+                    .ok()
+                    .filter(|exposure: &f64| exposure.is_finite())
+                    .ok_or_else(|| CliError::InvalidValue {
+                        option: option.to_string(),
+                        value: raw.clone(),
+                        reason: "expected a number of stops".to_string(),
+                    })?;
+            }
+            "--samples" => {
+                let raw = value()?;
+                anti_aliasing.samples = raw
+                    .parse()
+                    .ok()
+                    .filter(|samples| *samples > 0)
+                    .ok_or_else(|| CliError::InvalidValue {
+                        option: option.to_string(),
+                        value: raw.clone(),
+                        reason: "expected a positive integer".to_string(),
+                    })?;
+            }
+            "--pattern" => {
+                let name = value()?;
+                anti_aliasing.pattern = SamplePattern::from_name(&name).ok_or_else(|| CliError::InvalidValue {
+                    option: option.to_string(),
+                    value: name.clone(),
+                    reason: "expected jittered or stratified".to_string(),
+                })?;
+            }
+            "--filter" => {
+                let name = value()?;
+                anti_aliasing.filter = Filter::from_name(&name).ok_or_else(|| CliError::InvalidValue {
+                    option: option.to_string(),
+                    value: name.clone(),
+                    reason: "expected one of box, tent, gaussian, mitchell".to_string(),
+                })?;
+            }
+            "--max-samples" => {
+                let raw = value()?;
+                adaptive.get_or_insert_with(Adaptive::default).max_samples = raw
+                    .parse()
+                    .ok()
+                    .filter(|samples| *samples > 0)
+                    .ok_or_else(|| CliError::InvalidValue {
+                        option: option.to_string(),
+                        value: raw.clone(),
+                        reason: "expected a positive integer".to_string(),
+                    })?;
+            }
+            "--noise-threshold" | "--contrast-threshold" => {
+                let raw = value()?;
+                let threshold = parse_threshold(option, &raw)?;
+                let adaptive = adaptive.get_or_insert_with(Adaptive::default);
+                if option == "--noise-threshold" {
+                    adaptive.noise_threshold = threshold;
+                } else {
+                    adaptive.contrast_threshold = threshold;
+                }
+            }
+            "--sample-map" => sample_map = Some(PathBuf::from(value()?)),
+            "--gamma" => {
+                let raw = value()?;
+                tone_mapping.transfer = match raw.as_str() {
+                    "srgb" => TransferFunction::Srgb,
+                    _ => raw
+                        .parse()
+                        .ok()
+                        .filter(|gamma: &f64| gamma.is_finite() && *gamma > 0.0)
+                        .map(TransferFunction::Gamma)
+                        .ok_or_else(|| CliError::InvalidValue {
+                            option: option.to_string(),
+                            value: raw.clone(),
+                            reason: "expected 'srgb' or a positive number".to_string(),
+                        })?,
+                };
+            }
+            _ => return Err(CliError::UnknownOption(arg.clone())),
+        }
+    }
+
+    let min_samples = anti_aliasing.filter.min_samples();
+    if anti_aliasing.samples < min_samples {
+        return Err(CliError::InvalidValue {
+            option: "--samples".to_string(),
+            value: anti_aliasing.samples.to_string(),
+            reason: format!(
+                "the {} filter needs at least {} samples to cover its support",
+                anti_aliasing.filter.name(),
+                min_samples
+            ),
+        });
+    }
+
+    if let Some(adaptive) = adaptive {
+        if adaptive.max_samples < anti_aliasing.samples {
+            return Err(CliError::InvalidValue {
+                option: "--max-samples".to_string(),
+                value: adaptive.max_samples.to_string(),
+                reason: format!("must be at least --samples ({})", anti_aliasing.samples),
+            });
+        }
+        anti_aliasing.adaptive = Some(adaptive);
+    }
+
+    let out = out.unwrap_or_else(|| {
+        let extension = format.unwrap_or(OutputFormat::Png).extension();
+        PathBuf::from(format!("{}.{}", stage.name(), extension))
+    });
+    let format = match format {
+        Some(format) => format,
+        None if out.as_os_str() == "-" => OutputFormat::Ppm,
+        None => OutputFormat::from_path(&out).ok_or_else(|| CliError::InvalidValue {
+            option: "--out".to_string(),
+            value: out.display().to_string(),
+            reason: "unrecognized image file extension, use --format to choose one".to_string(),
+        })?,
+    };
+
+    Ok(Command::Render(RenderArgs {
+        scene,
+        width,
+        height,
+        out,
+        format,
+        stage,
+        depth,
+        fov_axis,
+        threads,
+        tone_mapping,
+        anti_aliasing,
+        sample_map,
+    }))
+}
+
+fn parse_threshold(option: &str, raw: &str) -> Result<f64, CliError> {
+    raw.parse()
+        .ok()
+        .filter(|threshold: &f64| threshold.is_finite() && *threshold >= 0.0)
+        .ok_or_else(|| CliError::InvalidValue {
+            option: option.to_string(),
+            value: raw.to_string(),
+            reason: "expected a non-negative number".to_string(),
+        })
+}
+
+fn parse_dimension(option: &str, raw: &str) -> Result<u32, CliError> {
+    let invalid = |reason: String| CliError::InvalidValue {
+        option: option.to_string(),
+        value: raw.to_string(),
+        reason,
+    };
+    let value: u32 = raw
+        .parse()
+        .map_err(|_| invalid("expected a positive integer".to_string()))?;
+    if value == 0 || value > MAX_DIMENSION {
+        return Err(invalid(format!("must be between 1 and {}", MAX_DIMENSION)));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &[&str]) -> Result<Command, CliError> {
+        let args: Vec<String> = ["render"].iter().chain(args).map(|arg| arg.to_string()).collect();
+        parse(&args)
+    }
+
+    fn render_args(args: &[&str]) -> RenderArgs {
+        match parse_args(args) {
+            Ok(Command::Render(args)) => args,
+            Ok(Command::Help) => panic!("parsed as help"),
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    fn error(args: &[&str]) -> String {
+        match parse_args(args) {
+            Err(err) => err.to_string(),
+            Ok(_) => panic!("{:?} parsed", args),
+        }
+    }
+
+    #[test]
+    fn defaults_and_both_value_syntaxes() {
+        let args = render_args(&[]);
+        assert_eq!((args.width, args.height), (400, 400));
+        assert_eq!(args.out, PathBuf::from("reflections.png"));
+        assert_eq!(args.depth, None);
+
+        let args = render_args(&["--width", "640", "--height=480", "--out", "frame.exr", "--depth=5"]);
+        assert_eq!((args.width, args.height), (640, 480));
+        assert_eq!(args.format, OutputFormat::Exr);
+        assert_eq!(args.depth, Some(5));
+    }
+
+    #[test]
+    fn rejects_out_of_range_dimensions() {
+        for (option, value) in [("--width", "0"), ("--height", "16385"), ("--width", "-1"), ("--height", "wide")] {
+            let message = error(&[option, value]);
+            assert!(message.starts_with(&format!("invalid value '{}' for '{}'", value, option)), "{}", message);
+        }
+        assert_eq!(render_args(&["--width", "16384"]).width, 16384);
+    }
+
+    #[test]
+    fn rejects_zero_samples() {
+        assert_eq!(error(&["--samples", "0"]), "invalid value '0' for '--samples': expected a positive integer");
+        assert_eq!(render_args(&["--samples", "4"]).anti_aliasing.samples, 4);
+    }
+
+    #[test]
+    fn rejects_too_few_samples_for_wide_filters() {
+        assert!(error(&["--filter", "mitchell"]).contains("needs at least 16 samples"));
+        assert!(error(&["--filter", "tent", "--samples", "3"]).contains("needs at least 4 samples"));
+        assert_eq!(render_args(&["--filter", "gaussian", "--samples", "9"]).anti_aliasing.filter, Filter::Gaussian);
+    }
+
+    #[test]
+    fn rejects_max_samples_below_samples() {
+        assert_eq!(
+            error(&["--samples", "8", "--max-samples", "4"]),
+            "invalid value '4' for '--max-samples': must be at least --samples (8)"
+        );
+        let args = render_args(&["--samples", "4", "--max-samples", "4"]);
+        assert_eq!(args.anti_aliasing.adaptive.map(|adaptive| adaptive.max_samples), Some(4));
+    }
+
+    #[test]
+    fn parses_gamma() {
+        assert_eq!(render_args(&["--gamma", "srgb"]).tone_mapping.transfer, TransferFunction::Srgb);
+        assert_eq!(render_args(&["--gamma", "2.2"]).tone_mapping.transfer, TransferFunction::Gamma(2.2));
+        for gamma in ["0", "-2.2", "inf", "NaN", "linear", ""] {
+            let message = error(&["--gamma", gamma]);
+            assert!(message.ends_with("expected 'srgb' or a positive number"), "{}", message);
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_options_and_missing_values() {
+        assert_eq!(error(&["--wdith", "10"]), "unknown option '--wdith'");
+        assert_eq!(error(&["--width"]), "option '--width' requires a value");
+        assert!(error(&["--out", "frame.xyz"]).contains("unrecognized image file extension"));
+        assert!(matches!(parse(&["draw".to_string()]), Err(CliError::UnknownCommand(_))));
+        assert!(matches!(parse(&[]), Err(CliError::MissingCommand)));
+        assert!(matches!(parse_args(&["--help"]), Ok(Command::Help)));
+    }
+}
@@ -1,7 +1,13 @@
 //! Core data structures and utilities for ray tracing.
 
+use crate::bump::Bump;
+use crate::hittable::HitRecord;
+use crate::plane::Plane;
+use crate::texture::{Pattern, PatternTexture, Texture, TextureMap, TextureSpace};
+use crate::tonemap::TransferFunction;
 use std::f64;
 
+#[derive(Clone, Copy)]
 pub struct Vec3 {
     pub x: f64,
     pub y: f64,
@@ -17,6 +23,14 @@ impl Vec3 {
         self.x * other.x + self.y * other.y + self.z * other.z
     }
 
+    pub fn cross(&self, other: &Vec3) -> Vec3 {
+        Vec3::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
     pub fn sub(&self, other: &Vec3) -> Vec3 {
         Vec3::new(self.x - other.x, self.y - other.y, self.z - other.z)
     }
@@ -39,36 +53,119 @@ impl Vec3 {
     }
 }
 
+/// A linear RGB color. Channels are nominally in `[0, 1]`, but radiance computed during
+/// shading may exceed 1; it is only brought into range by tone mapping for output.
 #[derive(Clone, Copy)]
 pub struct Color {
-    pub r: u8,
-    pub g: u8,
-    pub b: u8,
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
 }
 
 impl Color {
-    pub fn new(r: u8, g: u8, b: u8) -> Self {
+    pub fn new(r: f64, g: f64, b: f64) -> Self {
         Color { r, g, b }
     }
 
+    /// Decode 8-bit sRGB channels, the way colors are written in scene files, into linear
+    /// color.
+    pub fn from_srgb8(r: u8, g: u8, b: u8) -> Self {
+        let channel = |c: u8| TransferFunction::Srgb.decode(c as f64 / 255.0);
+        Color::new(channel(r), channel(g), channel(b))
+    }
+
     pub fn scale(&self, t: f64) -> Color {
-        Color::new(
-            (self.r as f64 * t).min(255.0) as u8,
-            (self.g as f64 * t).min(255.0) as u8,
-            (self.b as f64 * t).min(255.0) as u8,
-        )
+        Color::new(self.r * t, self.g * t, self.b * t)
     }
 
-    // TODO: might need saturating add here
     pub fn add(&self, other: &Color) -> Color {
         Color::new(self.r + other.r, self.g + other.g, self.b + other.b)
     }
+
+    /// Linear interpolation, `self` at `t = 0` and `other` at `t = 1`.
+    pub fn lerp(&self, other: &Color, t: f64) -> Color {
+        self.scale(1.0 - t).add(&other.scale(t))
+    }
+
+    /// Relative luminance of a linear color (Rec. 709 weights).
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
+    /// Component-wise product, e.g. a surface color lit by a colored light.
+    pub fn mul(&self, other: &Color) -> Color {
+        Color::new(self.r * other.r, self.g * other.g, self.b * other.b)
+    }
 }
 
+#[derive(Clone)]
 pub struct Material {
     pub color: Color,
+    /// Varies the color over the surface, replacing `color` when set.
+    pub texture: Option<Texture>,
     pub specular: Option<f64>,
+    /// Varies the specular exponent over the surface, replacing `specular` when set.
+    pub specular_map: Option<TextureMap>,
     pub reflective: Option<f64>,
+    /// Varies the reflectivity over the surface, replacing `reflective` when set.
+    pub reflective_map: Option<TextureMap>,
+    /// Fraction of light passing through the surface, refracted by `refractive_index`.
+    pub transparency: Option<f64>,
+    /// Index of refraction of the material's interior, relative to the surrounding air.
+    pub refractive_index: f64,
+    /// Perturbs the shading normal, see [`Bump`].
+    pub bump: Option<Bump>,
+}
+
+impl Default for Material {
+    /// A plain white diffuse material.
+    fn default() -> Self {
+        Material {
+            color: Color::new(1.0, 1.0, 1.0),
+            texture: None,
+            specular: None,
+            specular_map: None,
+            reflective: None,
+            reflective_map: None,
+            transparency: None,
+            refractive_index: 1.0,
+            bump: None,
+        }
+    }
+}
+
+impl Material {
+    /// The surface color at a hit `point` with surface coordinates `uv`.
+    pub fn color_at(&self, point: &Vec3, uv: (f64, f64)) -> Color {
+        match &self.texture {
+            Some(texture) => texture.color(point, uv),
+            None => self.color,
+        }
+    }
+
+    /// The normal to shade `hit` with, perturbed by the material's bump map if it has one.
+    pub fn shading_normal(&self, hit: &HitRecord) -> Vec3 {
+        match &self.bump {
+            Some(bump) => bump.normal(hit),
+            None => hit.normal,
+        }
+    }
+
+    /// The specular exponent at a hit `point` with surface coordinates `uv`.
+    pub fn specular_at(&self, point: &Vec3, uv: (f64, f64)) -> Option<f64> {
+        match &self.specular_map {
+            Some(map) => Some(map.value(point, uv).max(0.0)),
+            None => self.specular,
+        }
+    }
+
+    /// The reflectivity at a hit `point` with surface coordinates `uv`.
+    pub fn reflective_at(&self, point: &Vec3, uv: (f64, f64)) -> Option<f64> {
+        match &self.reflective_map {
+            Some(map) => Some(map.value(point, uv).clamp(0.0, 1.0)),
+            None => self.reflective,
+        }
+    }
 }
 
 pub struct Sphere {
@@ -83,50 +180,131 @@ pub fn generate_default_spheres() -> Vec<Sphere> {
             center: Vec3::new(0.0, -1.0, 3.0),
             radius: 1.0,
             material: Material {
-                color: Color::new(255, 0, 0), // red
+                color: Color::new(1.0, 0.0, 0.0), // red
                 specular: Some(500.0), // shiny
                 reflective: Some(0.2), // a bit reflective
+                ..Material::default()
             },
         },
         Sphere {
             center: Vec3::new(2.0, 0.0, 4.0),
             radius: 1.0,
             material: Material {
-                color: Color::new(0, 0, 255), // blue
+                color: Color::new(0.0, 0.0, 1.0), // blue
                 specular: Some(500.0), // shiny
                 reflective: Some(0.3),
+                ..Material::default()
             },
         },
         Sphere {
             center: Vec3::new(-2.0, 0.0, 4.0),
             radius: 1.0,
             material: Material {
-                color: Color::new(0, 255, 0), // green
+                color: Color::new(0.0, 1.0, 0.0), // green
                 specular: Some(10.0), // a bit shiny
                 reflective: Some(0.4),
-            },
-        },
-        Sphere {
-            center: Vec3::new(0.0, -5001.0, 0.0),
-            radius: 5000.0,
-            material: Material {
-                color: Color::new(255, 255, 0), // yellow
-                specular: Some(1000.0), // very shiny
-                reflective: Some(0.5),
+                ..Material::default()
             },
         },
     ]
 }
 
+/// The ground of the basic scene, the plane y = -1.
+pub fn generate_default_ground() -> Plane {
+    Plane::new(
+        Vec3::new(0.0, -1.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        Material {
+            color: Color::new(1.0, 1.0, 0.0), // yellow
+            texture: Some(Texture::Pattern(PatternTexture::new(
+                Pattern::Checker, // yellow and dark gray tiles
+                [Color::new(1.0, 1.0, 0.0), Color::from_srgb8(40, 40, 40)],
+                1.0,
+                TextureSpace::World,
+            ))),
+            specular: Some(1000.0), // very shiny
+            reflective: Some(0.5),
+            ..Material::default()
+        },
+    )
+}
+
+/// How the intensity of a point light falls off with the distance `d` from it.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Attenuation {
+    /// The same intensity at any distance.
+    #[default]
+    None,
+    /// `1 / d`.
+    Linear,
+    /// `1 / d²`, the physical falloff.
+    InverseSquare,
+    /// `1 / (constant + linear * d + quadratic * d²)`.
+    Coefficients { constant: f64, linear: f64, quadratic: f64 },
+}
+
+impl Attenuation {
+    /// Factor scaling a light's intensity at `distance`.
+    pub fn factor(&self, distance: f64) -> f64 {
+        match self {
+            Attenuation::None => 1.0,
+            Attenuation::Linear => 1.0 / distance,
+            Attenuation::InverseSquare => 1.0 / (distance * distance),
+            Attenuation::Coefficients {
+                constant,
+                linear,
+                quadratic,
+            } => 1.0 / (constant + linear * distance + quadratic * distance * distance),
+        }
+    }
+
+    /// Like [`Attenuation::factor`], but 0 at and beyond `range`.
+    pub fn factor_within(&self, distance: f64, range: Option<f64>) -> f64 {
+        if range.is_some_and(|range| distance >= range) {
+            0.0
+        } else {
+            self.factor(distance)
+        }
+    }
+}
+
 pub enum LightType {
     Ambient,
     Directional { direction: Vec3 },
-    Point { position: Vec3 },
+    /// A light shining in all directions from `position`. Points at least `range` away
+    /// get no light from it, which saves their shadow rays, so it should be set
+    /// where the attenuated light becomes negligible.
+    Point {
+        position: Vec3,
+        attenuation: Attenuation,
+        range: Option<f64>,
+    },
+    /// A point light shining along `direction`: at full intensity within `inner_angle` of
+    /// it, fading smoothly to nothing at `outer_angle`. Angles are in degrees, measured
+    /// from `direction`.
+    Spot {
+        position: Vec3,
+        direction: Vec3,
+        inner_angle: f64,
+        outer_angle: f64,
+    },
+    /// A glowing sphere. Lighting is averaged over `samples` points on it, each with its own
+    /// shadow ray, which gives soft shadows.
+    Spherical { center: Vec3, radius: f64, samples: u32 },
+    /// A glowing parallelogram spanned by `edge_u` and `edge_v` from `corner`, lighting
+    /// both sides. Sampled like [`LightType::Spherical`].
+    Rectangular {
+        corner: Vec3,
+        edge_u: Vec3,
+        edge_v: Vec3,
+        samples: u32,
+    },
 }
 
 pub struct Light {
     pub light_type: LightType,
     pub intensity: f64,
+    /// Tint of the light, multiplied with `intensity` per channel.
     pub color: Color,
 }
 
@@ -148,8 +326,56 @@ impl Light {
     }
 
     pub fn new_point(position: Vec3, intensity: f64, color: Color) -> Self {
+        Light::new_attenuated_point(position, Attenuation::None, None, intensity, color)
+    }
+
+    pub fn new_attenuated_point(
+        position: Vec3,
+        attenuation: Attenuation,
+        range: Option<f64>,
+        intensity: f64,
+        color: Color,
+    ) -> Self {
+        Light {
+            light_type: LightType::Point {
+                position,
+                attenuation,
+                range,
+            },
+            intensity,
+            color,
+        }
+    }
+
+    pub fn new_spot(position: Vec3, direction: Vec3, inner_angle: f64, outer_angle: f64, intensity: f64, color: Color) -> Self {
+        Light {
+            light_type: LightType::Spot {
+                position,
+                direction,
+                inner_angle,
+                outer_angle,
+            },
+            intensity,
+            color,
+        }
+    }
+
+    pub fn new_spherical(center: Vec3, radius: f64, samples: u32, intensity: f64, color: Color) -> Self {
         Light {
-            light_type: LightType::Point { position },
+            light_type: LightType::Spherical { center, radius, samples },
+            intensity,
+            color,
+        }
+    }
+
+    pub fn new_rectangular(corner: Vec3, edge_u: Vec3, edge_v: Vec3, samples: u32, intensity: f64, color: Color) -> Self {
+        Light {
+            light_type: LightType::Rectangular {
+                corner,
+                edge_u,
+                edge_v,
+                samples,
+            },
             intensity,
             color,
         }
@@ -158,9 +384,9 @@ impl Light {
 
 pub fn generate_default_lights() -> Vec<Light> {
     vec![
-        Light::new_ambient(0.2, Color::new(255, 255, 255)),
-        Light::new_directional(Vec3::new(1.0, 4.0, 4.0), 0.2, Color::new(255, 255, 255)),
-        Light::new_point(Vec3::new(2.0, 1.0, 0.0), 0.6, Color::new(255, 255, 255)),
+        Light::new_ambient(0.2, Color::new(1.0, 1.0, 1.0)),
+        Light::new_directional(Vec3::new(1.0, 4.0, 4.0), 0.2, Color::new(1.0, 1.0, 1.0)),
+        Light::new_point(Vec3::new(2.0, 1.0, 0.0), 0.6, Color::new(1.0, 1.0, 1.0)),
     ]
 }
 
@@ -174,22 +400,17 @@ pub mod config {
 pub mod geometry {
     use super::*;
 
-    /// Convert canvas coordinates to viewport coordinates.
-    ///
-    /// # Arguments
-    ///
-    /// * `x` - The x-coordinate of the canvas (image buffer), assumed to be centered
-    /// * `y` - The y-coordinate of the canvas (image buffer), assumed to be centered
-    ///
-    /// # Returns
-    ///
-    /// Returns the viewport coordinates, with z = distance to projection plane (viewport).
-    pub fn canvas_to_viewport(x: i32, y: i32) -> Vec3 {
-        Vec3::new(
-            x as f64 * config::VIEWPORT_SIZE / config::CANVAS_WIDTH as f64,
-            -y as f64 * config::VIEWPORT_SIZE / config::CANVAS_HEIGHT as f64,
-            config::PROJECTION_PLANE_D,
-        )
+    /// Build two unit vectors that, with the unit `normal`, form a right-handed
+    /// orthonormal basis (tangent x bitangent = normal).
+    pub fn orthonormal_basis(normal: &Vec3) -> (Vec3, Vec3) {
+        let helper = if normal.x.abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let bitangent = normal.cross(&helper).normalize();
+        let tangent = bitangent.cross(normal);
+        (tangent, bitangent)
     }
 
     pub fn intersect_ray_sphere(origin: &Vec3, direction: &Vec3, sphere: &Sphere) -> (f64, f64) {
@@ -213,18 +434,190 @@ pub mod geometry {
 
 pub mod scene {
     use super::*;
-    
+    use crate::bvh::{Bvh, BvhStats};
+    use crate::camera::Camera;
+    use crate::hittable::{HitRecord, Hittable};
+
     pub struct Scene {
-        pub spheres: Vec<Sphere>,
+        /// Kept private so the BVH can't go stale, see [`Scene::add_object`].
+        objects: Vec<Box<dyn Hittable>>,
         pub lights: Vec<Light>,
+        /// Overrides the background color of the shading options when set.
+        pub background: Option<Color>,
+        /// The viewpoint the scene was authored for.
+        pub camera: Camera,
+        /// Acceleration structure over the bounded objects, see [`build_bvh`].
+        bvh: Bvh,
+        /// Indices of objects without bounds, which are tested against every ray.
+        unbounded: Vec<usize>,
     }
 
     impl Scene {
-        pub fn basic_scene() -> Self {
+        /// Create a scene viewed from the default camera, building its BVH.
+        pub fn new(objects: Vec<Box<dyn Hittable>>, lights: Vec<Light>) -> Self {
+            let (bvh, unbounded) = build_bvh(&objects);
             Scene {
-                spheres: generate_default_spheres(),
-                lights: generate_default_lights(),
+                objects,
+                lights,
+                background: None,
+                camera: Camera::default(),
+                bvh,
+                unbounded,
             }
         }
+
+        pub fn basic_scene() -> Self {
+            let mut objects: Vec<Box<dyn Hittable>> = generate_default_spheres()
+                .into_iter()
+                .map(|sphere| Box::new(sphere) as Box<dyn Hittable>)
+                .collect();
+            objects.push(Box::new(generate_default_ground()));
+            Scene::new(objects, generate_default_lights())
+        }
+
+        pub fn objects(&self) -> &[Box<dyn Hittable>] {
+            &self.objects
+        }
+
+        /// Add an object, rebuilding the BVH. Scenes with many objects are better built
+        /// in one go with [`Scene::new`].
+        pub fn add_object(&mut self, object: Box<dyn Hittable>) {
+            self.objects.push(object);
+            self.rebuild_bvh();
+        }
+
+        /// Remove and return the object at `index`, rebuilding the BVH.
+        ///
+        /// # Panics
+        ///
+        /// Panics if `index` is out of bounds.
+        pub fn remove_object(&mut self, index: usize) -> Box<dyn Hittable> {
+            let object = self.objects.remove(index);
+            self.rebuild_bvh();
+            object
+        }
+
+        fn rebuild_bvh(&mut self) {
+            (self.bvh, self.unbounded) = build_bvh(&self.objects);
+        }
+
+        /// Statistics of the current BVH.
+        pub fn bvh_stats(&self) -> &BvhStats {
+            self.bvh.stats()
+        }
+
+        /// Find the closest intersection between a ray and all objects in the scene.
+        pub fn closest_hit(&self, origin: &Vec3, direction: &Vec3, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+            let mut closest = self.closest_hit_in(self.unbounded.iter().copied(), origin, direction, t_min, t_max);
+            let closest_t = closest.as_ref().map_or(t_max, |hit| hit.t);
+            if let Some(hit) = self.bvh.closest_hit(origin, direction, t_min, closest_t, |i, t_min, t_max| {
+                self.objects[i].hit(origin, direction, t_min, t_max)
+            }) {
+                closest = Some(hit);
+            }
+            closest
+        }
+
+        fn closest_hit_in(
+            &self,
+            indices: impl Iterator<Item = usize>,
+            origin: &Vec3,
+            direction: &Vec3,
+            t_min: f64,
+            t_max: f64,
+        ) -> Option<HitRecord<'_>> {
+            let mut closest = None;
+            let mut closest_t = t_max;
+
+            for i in indices {
+                if let Some(hit) = self.objects[i].hit(origin, direction, t_min, closest_t) {
+                    closest_t = hit.t;
+                    closest = Some(hit);
+                }
+            }
+
+            closest
+        }
+
+        /// Check whether a ray hits any object, e.g. to test for shadows.
+        pub fn any_hit(&self, origin: &Vec3, direction: &Vec3, t_min: f64, t_max: f64) -> bool {
+            let hits = |i: usize, t_min: f64, t_max: f64| self.objects[i].hit_t(origin, direction, t_min, t_max).is_some();
+            self.unbounded.iter().any(|&i| hits(i, t_min, t_max)) || self.bvh.any_hit(origin, direction, t_min, t_max, hits)
+        }
+    }
+
+    /// Build a BVH over the bounded `objects`, also returning the indices of the objects
+    /// without bounds.
+    fn build_bvh(objects: &[Box<dyn Hittable>]) -> (Bvh, Vec<usize>) {
+        let mut bounds = Vec::with_capacity(objects.len());
+        let mut bounded = Vec::with_capacity(objects.len());
+        let mut unbounded = Vec::new();
+        for (i, object) in objects.iter().enumerate() {
+            match object.bounding_box() {
+                Some(aabb) => {
+                    bounds.push(aabb);
+                    bounded.push(i);
+                }
+                None => unbounded.push(i),
+            }
+        }
+
+        let mut bvh = Bvh::build(&bounds);
+        bvh.remap(&bounded);
+        (bvh, unbounded)
     }
-}
\ No newline at end of file
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn added_and_removed_objects_are_seen_by_rays() {
+            let mut scene = Scene::new(Vec::new(), Vec::new());
+            let (origin, direction) = (Vec3::new(0.0, 5.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+            assert!(scene.closest_hit(&origin, &direction, 1.0, f64::INFINITY).is_none());
+
+            scene.add_object(Box::new(Sphere {
+                center: Vec3::new(0.0, 5.0, 10.0),
+                radius: 1.0,
+                material: Material::default(),
+            }));
+            let hit = scene.closest_hit(&origin, &direction, 1.0, f64::INFINITY);
+            assert_eq!(hit.map(|hit| hit.t), Some(9.0));
+            assert!(scene.any_hit(&origin, &direction, 1.0, f64::INFINITY));
+
+            scene.remove_object(0);
+            assert!(scene.objects().is_empty());
+            assert!(!scene.any_hit(&origin, &direction, 1.0, f64::INFINITY));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attenuation_follows_its_formula() {
+        assert_eq!(Attenuation::None.factor(7.0), 1.0);
+        assert_eq!(Attenuation::Linear.factor(4.0), 0.25);
+        assert_eq!(Attenuation::InverseSquare.factor(4.0), 1.0 / 16.0);
+        let coefficients = Attenuation::Coefficients {
+            constant: 1.0,
+            linear: 0.5,
+            quadratic: 0.25,
+        };
+        assert_eq!(coefficients.factor(0.0), 1.0);
+        // 1 + 0.5 * 2 + 0.25 * 4
+        assert_eq!(coefficients.factor(2.0), 1.0 / 3.0);
+    }
+
+    #[test]
+    fn attenuation_cuts_off_at_the_range() {
+        let attenuation = Attenuation::Linear;
+        assert_eq!(attenuation.factor_within(2.0, None), 0.5);
+        assert_eq!(attenuation.factor_within(2.0, Some(2.5)), 0.5);
+        assert_eq!(attenuation.factor_within(2.5, Some(2.5)), 0.0);
+        assert_eq!(attenuation.factor_within(100.0, Some(2.5)), 0.0);
+    }
+}
@@ -0,0 +1,142 @@
+//! Ray-object intersection shared by every kind of geometry.
+
+use crate::bvh::Aabb;
+use crate::common::{geometry, Material, Sphere, Vec3};
+use std::f64::consts::PI;
+
+/// Details of a ray-object intersection.
+pub struct HitRecord<'a> {
+    /// Ray parameter of the hit, `point = origin + t * direction`.
+    pub t: f64,
+    pub point: Vec3,
+    /// Unit surface normal, always facing against the incoming ray.
+    pub normal: Vec3,
+    pub material: &'a Material,
+    /// Surface coordinates of the hit, each in `[0, 1]`.
+    pub uv: (f64, f64),
+    /// Whether the ray hit the outside of the surface.
+    pub front_face: bool,
+    /// See [`HitRecord::derivatives`].
+    derivatives: Option<(Vec3, Vec3)>,
+}
+
+impl<'a> HitRecord<'a> {
+    /// Build a hit record from the geometric outward normal, flipping it to face the ray.
+    pub fn new(
+        t: f64,
+        point: Vec3,
+        direction: &Vec3,
+        outward_normal: Vec3,
+        material: &'a Material,
+        uv: (f64, f64),
+    ) -> Self {
+        // grazing hits (perpendicular normal) count as front facing
+        let front_face = direction.dot(&outward_normal) <= 0.0;
+        let normal = if front_face {
+            outward_normal
+        } else {
+            outward_normal.scale(-1.0)
+        };
+        HitRecord {
+            t,
+            point,
+            normal,
+            material,
+            uv,
+            front_face,
+            derivatives: None,
+        }
+    }
+
+    /// Set the surface derivatives, see [`HitRecord::derivatives`].
+    pub fn with_derivatives(mut self, dpdu: Vec3, dpdv: Vec3) -> Self {
+        self.derivatives = Some((dpdu, dpdv));
+        self
+    }
+
+    /// Partial derivatives of the point with respect to `u` and `v`, spanning the tangent
+    /// plane. They orient bump and normal maps. Without ones set by the geometry, an
+    /// arbitrary tangent frame.
+    pub fn derivatives(&self) -> (Vec3, Vec3) {
+        self.derivatives
+            .unwrap_or_else(|| geometry::orthonormal_basis(&self.outward_normal()))
+    }
+
+    /// The normal on the outside of the surface, whichever side was hit.
+    pub fn outward_normal(&self) -> Vec3 {
+        if self.front_face {
+            self.normal
+        } else {
+            self.normal.scale(-1.0)
+        }
+    }
+}
+
+/// Geometry that can be intersected by a ray.
+pub trait Hittable: Send + Sync {
+    /// Find the closest intersection with `t` in `[t_min, t_max]`.
+    fn hit(&self, origin: &Vec3, direction: &Vec3, t_min: f64, t_max: f64) -> Option<HitRecord<'_>>;
+
+    /// The ray parameter of the closest intersection with `t` in `[t_min, t_max]`, for
+    /// queries such as shadow rays that don't need a full [`HitRecord`].
+    fn hit_t(&self, origin: &Vec3, direction: &Vec3, t_min: f64, t_max: f64) -> Option<f64> {
+        self.hit(origin, direction, t_min, t_max).map(|hit| hit.t)
+    }
+
+    /// Bounds used to place the object in the scene's BVH, `None` for unbounded objects
+    /// such as infinite planes.
+    fn bounding_box(&self) -> Option<Aabb>;
+}
+
+impl Hittable for Sphere {
+    fn hit(&self, origin: &Vec3, direction: &Vec3, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let t = self.hit_t(origin, direction, t_min, t_max)?;
+        let point = origin.add(&direction.scale(t));
+        let outward_normal = point.sub(&self.center).normalize();
+        let (dpdu, dpdv) = sphere_derivatives(&outward_normal, self.radius);
+        Some(
+            HitRecord::new(
+                t,
+                point,
+                direction,
+                outward_normal,
+                &self.material,
+                sphere_uv(&outward_normal),
+            )
+            .with_derivatives(dpdu, dpdv),
+        )
+    }
+
+    fn hit_t(&self, origin: &Vec3, direction: &Vec3, t_min: f64, t_max: f64) -> Option<f64> {
+        let (t1, t2) = geometry::intersect_ray_sphere(origin, direction, self);
+        [t2, t1]
+            .into_iter()
+            .find(|t| t.is_finite() && *t >= t_min && *t <= t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        Some(Aabb::new(self.center.sub(&r), self.center.add(&r)))
+    }
+}
+
+/// Spherical coordinates of a point on the unit sphere.
+///
+/// `u` wraps around the Y axis starting at -X and passing -Z, +X and +Z in that order, so
+/// an image mapped onto the sphere isn't mirrored when seen from outside. `v` goes from
+/// the bottom pole (0) to the top pole (1).
+pub fn sphere_uv(n: &Vec3) -> (f64, f64) {
+    let phi = n.z.atan2(n.x) + PI;
+    let theta = (-n.y).clamp(-1.0, 1.0).acos();
+    (phi / (2.0 * PI), theta / PI)
+}
+
+/// Derivatives of a point on a sphere of `radius` with respect to the coordinates of
+/// [`sphere_uv`], at unit normal `n`.
+fn sphere_derivatives(n: &Vec3, radius: f64) -> (Vec3, Vec3) {
+    let phi = n.z.atan2(n.x);
+    let (sin_theta, cos_theta) = ((n.x * n.x + n.z * n.z).sqrt(), -n.y);
+    let dpdu = Vec3::new(-n.z, 0.0, n.x).scale(2.0 * PI * radius);
+    let dpdv = Vec3::new(cos_theta * phi.cos(), sin_theta, cos_theta * phi.sin()).scale(PI * radius);
+    (dpdu, dpdv)
+}
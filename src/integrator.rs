@@ -0,0 +1,766 @@
+//! Unified ray tracing integrator.
+//!
+//! The render stages that used to live in separate modules (basic, lights, specular,
+//! shadows, reflections) are now presets of [`ShadingOptions`], selected at runtime.
+
+use crate::camera::Camera;
+use crate::common::{self, scene::Scene, *};
+use crate::hittable::HitRecord;
+use crate::sampling::{Adaptive, AntiAliasing, PixelEstimate, Rng, SampleCounts, SamplePattern};
+use crate::tonemap::{tone_map, ToneMapping};
+use image::{ImageBuffer, Rgb, Rgb32FImage, RgbImage};
+use std::f64::consts::PI;
+use std::sync::Mutex;
+use std::thread;
+
+/// Runtime switches for the shading pipeline.
+#[derive(Clone, Copy)]
+pub struct ShadingOptions {
+    /// Shade surfaces with ambient and diffuse light. When both this and `specular`
+    /// are off, surfaces are drawn in their flat material color.
+    pub diffuse: bool,
+    /// Add specular highlights for materials with a specular exponent.
+    pub specular: bool,
+    /// Cast shadow rays towards point and directional lights.
+    pub shadows: bool,
+    /// Trace reflection rays for reflective materials.
+    pub reflections: bool,
+    /// Trace refraction rays (and Fresnel reflections) for transparent materials.
+    pub refractions: bool,
+    /// Maximum number of reflection bounces.
+    pub max_depth: u32,
+    /// Color returned for rays that hit nothing.
+    pub background: Color,
+}
+
+/// The render stages of the original step-by-step ray tracer.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Basic,
+    Lights,
+    Specular,
+    Shadows,
+    Reflections,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 5] = [
+        Stage::Basic,
+        Stage::Lights,
+        Stage::Specular,
+        Stage::Shadows,
+        Stage::Reflections,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::Basic => "basic",
+            Stage::Lights => "lights",
+            Stage::Specular => "specular",
+            Stage::Shadows => "shadows",
+            Stage::Reflections => "reflections",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Stage> {
+        Stage::ALL.into_iter().find(|stage| stage.name() == name)
+    }
+
+    /// Shading options reproducing this stage.
+    pub fn options(&self) -> ShadingOptions {
+        let white = Color::new(1.0, 1.0, 1.0);
+        match self {
+            Stage::Basic => ShadingOptions {
+                diffuse: false,
+                specular: false,
+                shadows: false,
+                reflections: false,
+                refractions: false,
+                max_depth: 0,
+                background: white,
+            },
+            Stage::Lights => ShadingOptions {
+                diffuse: true,
+                ..Stage::Basic.options()
+            },
+            Stage::Specular => ShadingOptions {
+                specular: true,
+                ..Stage::Lights.options()
+            },
+            Stage::Shadows => ShadingOptions {
+                shadows: true,
+                ..Stage::Specular.options()
+            },
+            Stage::Reflections => ShadingOptions {
+                reflections: true,
+                refractions: true,
+                max_depth: 3,
+                background: Color::new(0.0, 0.0, 0.0),
+                ..Stage::Shadows.options()
+            },
+        }
+    }
+}
+
+/// Compute the light arriving at a point, per color channel.
+fn compute_lighting(
+    point: &Vec3,
+    normal: &Vec3,
+    view: &Vec3,
+    specular: Option<f64>,
+    scene: &Scene,
+    options: &ShadingOptions,
+) -> Color {
+    scene.lights.iter().fold(Color::new(0.0, 0.0, 0.0), |lighting, light| {
+        // diffuse and specular intensity of light arriving from `direction`, with shadow
+        // rays up to `t_max`; lights at a position sit at t = 1 along the unnormalized
+        // direction towards them
+        let shade = |direction: &Vec3, t_max: f64| {
+            if options.shadows && scene.any_hit(point, direction, 0.001, t_max) {
+                return 0.0;
+            }
+            let diffuse = if options.diffuse {
+                calculate_diffuse_intensity(normal, direction, light.intensity)
+            } else {
+                0.0
+            };
+            let specular = if options.specular {
+                calculate_specular_intensity(normal, direction, view, specular, light.intensity)
+            } else {
+                0.0
+            };
+            diffuse + specular
+        };
+        // average over stratified sample positions on an area light, mapped from the unit
+        // square
+        let area = |samples: u32, position: &dyn Fn(f64, f64) -> Vec3| {
+            let mut rng = Rng::for_point(point);
+            let count = samples.max(1);
+            let total: f64 = (0..count)
+                .map(|index| {
+                    let (a, b) = SamplePattern::Stratified.sample(index, count, &mut rng);
+                    shade(&position(a, b).sub(point), 1.0)
+                })
+                .sum();
+            total / count as f64
+        };
+
+        let intensity = match &light.light_type {
+            LightType::Ambient => return lighting.add(&light.color.scale(light.intensity)),
+            LightType::Point {
+                position,
+                attenuation,
+                range,
+            } => {
+                let direction = position.sub(point);
+                let factor = attenuation.factor_within(direction.length(), *range);
+                // skip the shadow ray out of range
+                if factor > 0.0 { factor * shade(&direction, 1.0) } else { 0.0 }
+            }
+            LightType::Directional { direction } => shade(direction, f64::INFINITY),
+            LightType::Spot {
+                position,
+                direction: axis,
+                inner_angle,
+                outer_angle,
+            } => {
+                let direction = position.sub(point);
+                let falloff = spot_falloff(&direction.scale(-1.0), axis, *inner_angle, *outer_angle);
+                // skip the shadow ray outside the cone
+                if falloff > 0.0 {
+                    falloff * shade(&direction, 1.0)
+                } else {
+                    0.0
+                }
+            }
+            LightType::Spherical { center, radius, samples } => {
+                // sample the disk facing the point, which covers (nearly) the same
+                // directions as the sphere
+                let (tangent, bitangent) = common::geometry::orthonormal_basis(&center.sub(point).normalize());
+                area(*samples, &|a, b| {
+                    let (r, angle) = (radius * a.sqrt(), 2.0 * PI * b);
+                    center.add(&tangent.scale(r * angle.cos())).add(&bitangent.scale(r * angle.sin()))
+                })
+            }
+            LightType::Rectangular {
+                corner,
+                edge_u,
+                edge_v,
+                samples,
+            } => area(*samples, &|a, b| corner.add(&edge_u.scale(a)).add(&edge_v.scale(b))),
+        };
+        lighting.add(&light.color.scale(intensity))
+    })
+}
+
+/// Fraction of a spotlight's intensity shining along `ray` (from the light), given the
+/// spotlight's `axis` and cone half-angles in degrees. A smoothstep of the cosine
+/// between the cones.
+fn spot_falloff(ray: &Vec3, axis: &Vec3, inner_angle: f64, outer_angle: f64) -> f64 {
+    let cos = ray.dot(axis) / (ray.length() * axis.length());
+    let (cos_inner, cos_outer) = (inner_angle.to_radians().cos(), outer_angle.to_radians().cos());
+    if cos_inner <= cos_outer {
+        // equal cones give a hard edge
+        return if cos >= cos_outer { 1.0 } else { 0.0 };
+    }
+    let t = ((cos - cos_outer) / (cos_inner - cos_outer)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Calculate the diffuse lighting intensity based on the normal and light direction
+fn calculate_diffuse_intensity(normal: &Vec3, direction: &Vec3, intensity: f64) -> f64 {
+    let n_dot_l = normal.dot(direction);
+    if n_dot_l <= 0.0 {
+        return 0.0;
+    }
+    intensity * n_dot_l / (normal.length() * direction.length())
+}
+
+/// Calculate the specular lighting intensity
+fn calculate_specular_intensity(normal: &Vec3, light_dir: &Vec3, view: &Vec3, specular: Option<f64>, intensity: f64) -> f64 {
+    if let Some(s) = specular {
+        let n_dot_l = normal.dot(light_dir);
+        if n_dot_l > 0.0 {
+            // R = 2 * N * dot(N, L) - L
+            let reflection = normal.scale(2.0 * n_dot_l).sub(light_dir);
+            let r_dot_v = reflection.dot(view);
+
+            if r_dot_v > 0.0 {
+                return intensity * (r_dot_v / (reflection.length() * view.length())).powf(s);
+            }
+        }
+    }
+    0.0
+}
+
+/// Reflect a ray off a surface
+fn reflect_ray(normal: &Vec3, ray: &Vec3) -> Vec3 {
+    normal.scale(2.0 * normal.dot(ray)).sub(ray)
+}
+
+/// Refract a ray through a surface with Snell's law.
+///
+/// # Arguments
+///
+/// * `normal` - The unit surface normal, facing against the ray.
+/// * `direction` - The direction of the incoming ray.
+/// * `refractive_index` - The index of refraction of the material's interior.
+/// * `entering` - Whether the ray enters the material, rather than leaving it.
+///
+/// # Returns
+///
+/// Returns the unit refracted direction and the fraction of light reflected instead,
+/// from Schlick's approximation of the Fresnel equations. Returns `None` on total
+/// internal reflection.
+fn refract_ray(normal: &Vec3, direction: &Vec3, refractive_index: f64, entering: bool) -> Option<(Vec3, f64)> {
+    let unit = direction.normalize();
+    let eta = if entering { 1.0 / refractive_index } else { refractive_index };
+    let cos_i = (-unit.dot(normal)).clamp(0.0, 1.0);
+    let sin2_t = eta * eta * (1.0 - cos_i * cos_i);
+    if sin2_t > 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let refracted = unit.scale(eta).add(&normal.scale(eta * cos_i - cos_t));
+
+    // Schlick uses the angle on the optically thinner side
+    let r0 = ((refractive_index - 1.0) / (refractive_index + 1.0)).powi(2);
+    let cos = if entering { cos_i } else { cos_t };
+    let reflectance = r0 + (1.0 - r0) * (1.0 - cos).powi(5);
+    Some((refracted, reflectance))
+}
+
+/// Trace a ray through the scene and compute the color at the intersection point.
+///
+/// # Arguments
+///
+/// * `origin` - The origin of the ray.
+/// * `direction` - The direction of the ray.
+/// * `t_min` - The minimum distance to consider for intersections.
+/// * `t_max` - The maximum distance to consider for intersections.
+/// * `scene` - The objects and lights to render.
+/// * `options` - Which shading features are enabled.
+/// * `recursion_depth` - Remaining number of reflection bounces.
+///
+/// # Returns
+///
+/// Returns the shaded color of the closest intersection, or the background color.
+pub fn trace(
+    origin: &Vec3,
+    direction: &Vec3,
+    t_min: f64,
+    t_max: f64,
+    scene: &Scene,
+    options: &ShadingOptions,
+    recursion_depth: u32,
+) -> Color {
+    scene
+        .closest_hit(origin, direction, t_min, t_max)
+        .map(|hit| {
+            let albedo = hit.material.color_at(&hit.point, hit.uv);
+            if !options.diffuse && !options.specular {
+                return albedo;
+            }
+
+            // the (possibly bump mapped) normal is used for lighting and secondary rays
+            let normal = hit.material.shading_normal(&hit);
+            let HitRecord {
+                point,
+                material,
+                uv,
+                front_face,
+                ..
+            } = hit;
+
+            // Calculate local color
+            let local_color = {
+                let lighting = compute_lighting(
+                    &point,
+                    &normal,
+                    &direction.scale(-1.0), // View direction (opposite of ray direction)
+                    material.specular_at(&point, uv),
+                    scene,
+                    options,
+                );
+                albedo.mul(&lighting)
+            };
+
+            if recursion_depth == 0 {
+                return local_color;
+            }
+
+            // Recursive call, shared by mirror and Fresnel reflections
+            let mut reflected = None;
+            let mut reflected_color = || {
+                *reflected.get_or_insert_with(|| {
+                    let reflected_ray = reflect_ray(&normal, &direction.scale(-1.0));
+                    trace(
+                        &point,
+                        &reflected_ray,
+                        0.001,
+                        f64::INFINITY,
+                        scene,
+                        options,
+                        recursion_depth - 1,
+                    )
+                })
+            };
+
+            let r = material.reflective_at(&point, uv).unwrap_or(0.0);
+            let mut color = local_color;
+            if options.reflections && r > 0.0 {
+                // Blend local and reflected colors based on reflectivity
+                color = color.scale(1.0 - r).add(&reflected_color().scale(r));
+            }
+
+            let transparency = material.transparency.unwrap_or(0.0);
+            if options.refractions && transparency > 0.0 {
+                let transmitted = match refract_ray(&normal, direction, material.refractive_index, front_face) {
+                    Some((refracted_ray, reflectance)) => {
+                        let refracted_color = trace(
+                            &point,
+                            &refracted_ray,
+                            0.001,
+                            f64::INFINITY,
+                            scene,
+                            options,
+                            recursion_depth - 1,
+                        );
+                        refracted_color
+                            .scale(1.0 - reflectance)
+                            .add(&reflected_color().scale(reflectance))
+                    }
+                    // total internal reflection
+                    None => reflected_color(),
+                };
+                color = color.scale(1.0 - transparency).add(&transmitted.scale(transparency));
+            }
+            color
+        })
+        .unwrap_or(scene.background.unwrap_or(options.background))
+}
+
+/// Image size and shading configuration for a render.
+#[derive(Clone, Copy)]
+pub struct RenderSettings {
+    pub width: u32,
+    pub height: u32,
+    pub shading: ShadingOptions,
+    /// Number of worker threads, 0 uses one per available core.
+    pub threads: usize,
+    /// Conversion of the rendered radiance to 8-bit colors, see [`render`].
+    pub tone_mapping: ToneMapping,
+    pub anti_aliasing: AntiAliasing,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            width: common::config::CANVAS_WIDTH,
+            height: common::config::CANVAS_HEIGHT,
+            shading: Stage::Reflections.options(),
+            threads: 0,
+            tone_mapping: ToneMapping::default(),
+            anti_aliasing: AntiAliasing::default(),
+        }
+    }
+}
+
+impl RenderSettings {
+    /// The number of threads to render with, resolving 0 to the number of cores.
+    pub fn thread_count(&self) -> usize {
+        match self.threads {
+            0 => thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        }
+    }
+}
+
+/// Edge length of the square tiles handed out to render threads.
+const TILE_SIZE: u32 = 32;
+
+/// A rectangular block of pixels, clipped to the image.
+#[derive(Clone, Copy)]
+struct Tile {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl Tile {
+    /// Coordinates of the tile's pixels in row-major order.
+    fn pixels(&self) -> impl Iterator<Item = (u32, u32)> {
+        let tile = *self;
+        (tile.y..tile.y + tile.height).flat_map(move |y| (tile.x..tile.x + tile.width).map(move |x| (x, y)))
+    }
+}
+
+/// Split a `width` x `height` image into tiles in row-major order.
+fn tiles(width: u32, height: u32) -> Vec<Tile> {
+    let mut tiles = Vec::new();
+    for y in (0..height).step_by(TILE_SIZE as usize) {
+        for x in (0..width).step_by(TILE_SIZE as usize) {
+            tiles.push(Tile {
+                x,
+                y,
+                width: TILE_SIZE.min(width - x),
+                height: TILE_SIZE.min(height - y),
+            });
+        }
+    }
+    tiles
+}
+
+/// Index of pixel `(x, y)` of a `width` x `height` image in a buffer holding the pixels
+/// of its [`tiles`] one tile after another, each in row-major order.
+fn tile_major_index(width: u32, height: u32, x: u32, y: u32) -> usize {
+    let (band_y, tile_x) = (y - y % TILE_SIZE, x - x % TILE_SIZE);
+    let band_height = TILE_SIZE.min(height - band_y) as usize;
+    let tile_width = TILE_SIZE.min(width - tile_x) as usize;
+    band_y as usize * width as usize + tile_x as usize * band_height + (y - band_y) as usize * tile_width + (x - tile_x) as usize
+}
+
+/// Run `render_tile` for every tile with its pixels in `data`, which holds them in tile
+/// order (see [`tile_major_index`]), on `threads` threads that pick up tiles one at a time.
+fn for_each_tile<T, F>(tiles: &[Tile], data: &mut [T], threads: usize, render_tile: F)
+where
+    T: Send,
+    F: Fn(&Tile, &mut [T]) + Sync,
+{
+    let mut work = Vec::with_capacity(tiles.len());
+    let mut rest = data;
+    for tile in tiles {
+        let (pixels, next) = rest.split_at_mut((tile.width * tile.height) as usize);
+        work.push((tile, pixels));
+        rest = next;
+    }
+
+    let threads = threads.min(tiles.len()).max(1);
+    if threads == 1 {
+        for (tile, pixels) in work {
+            render_tile(tile, pixels);
+        }
+        return;
+    }
+
+    let queue = Mutex::new(work.into_iter());
+    thread::scope(|s| {
+        for _ in 0..threads {
+            s.spawn(|| loop {
+                let Some((tile, pixels)) = queue.lock().expect("render thread panicked").next() else {
+                    break;
+                };
+                render_tile(tile, pixels);
+            });
+        }
+    });
+}
+
+/// Trace the primary ray through canvas point `(x, y)`.
+fn render_sample(scene: &Scene, camera: &Camera, settings: &RenderSettings, x: f64, y: f64) -> Color {
+    let (origin, direction) = camera.primary_ray(x, y, settings.width, settings.height);
+    trace(
+        &origin,
+        &direction,
+        1.0,
+        f64::INFINITY,
+        scene,
+        &settings.shading,
+        settings.shading.max_depth,
+    )
+}
+
+/// Sampling state of a pixel, kept between the passes of adaptive sampling.
+struct Pixel {
+    rng: Rng,
+    estimate: PixelEstimate,
+}
+
+/// Add a batch of `count` samples to pixel `(x, y)`, placed by the pattern and weighted
+/// by the filter of `settings.anti_aliasing`.
+fn add_samples(scene: &Scene, camera: &Camera, settings: &RenderSettings, x: u32, y: u32, pixel: &mut Pixel, count: u32) {
+    let (center_x, center_y) = (x as f64 + 0.5, y as f64 + 0.5);
+    let AntiAliasing { pattern, filter, .. } = settings.anti_aliasing;
+    if count == 1 && pixel.estimate.count() == 0 {
+        let color = render_sample(scene, camera, settings, center_x, center_y);
+        pixel.estimate.add(&color, filter.weight(0.0, 0.0));
+        return;
+    }
+
+    // spread the samples over the filter's support around the pixel center, once the
+    // pixel gets enough to cover it; fewer would leave the weights to a few samples,
+    // which the negative lobes of the Mitchell filter can nearly cancel
+    let covered = pixel.estimate.count() + count >= filter.min_samples();
+    let radius = if covered { filter.radius() } else { 0.5 };
+    for i in 0..count {
+        let (u, v) = pattern.sample(i, count, &mut pixel.rng);
+        let (dx, dy) = ((2.0 * u - 1.0) * radius, (2.0 * v - 1.0) * radius);
+        let color = render_sample(scene, camera, settings, center_x + dx, center_y + dy);
+        pixel.estimate.add(&color, filter.weight(dx, dy));
+    }
+}
+
+/// The number of samples to add to every pixel in the next adaptive pass, in the tile
+/// order of `pixels`: a batch for pixels that are noisy or contrast with a neighbor, 0 for
+/// the rest.
+fn refinement(pixels: &[Pixel], tiles: &[Tile], settings: &RenderSettings, adaptive: &Adaptive) -> Vec<u32> {
+    let (width, height) = (settings.width, settings.height);
+    let batch = settings.anti_aliasing.samples.max(1);
+    tiles
+        .iter()
+        .flat_map(Tile::pixels)
+        .zip(pixels)
+        .map(|((x, y), pixel)| {
+            let estimate = &pixel.estimate;
+            let remaining = adaptive.max_samples.saturating_sub(estimate.count());
+            if remaining == 0 {
+                return 0;
+            }
+            let neighbors = [
+                (x > 0).then(|| (x - 1, y)),
+                (x + 1 < width).then(|| (x + 1, y)),
+                (y > 0).then(|| (x, y - 1)),
+                (y + 1 < height).then(|| (x, y + 1)),
+            ];
+            let contrasting = neighbors.into_iter().flatten().any(|(nx, ny)| {
+                let neighbor = &pixels[tile_major_index(width, height, nx, ny)].estimate;
+                estimate.contrast(neighbor) > adaptive.contrast_threshold
+            });
+            if contrasting || estimate.standard_error() > adaptive.noise_threshold {
+                batch.min(remaining)
+            } else {
+                0
+            }
+        })
+        .collect()
+}
+
+/// Render the scene as seen from `camera` into an 8-bit image, tone mapped with
+/// `settings.tone_mapping`.
+pub fn render(scene: &Scene, camera: &Camera, settings: &RenderSettings) -> RgbImage {
+    tone_map(&render_hdr(scene, camera, settings), &settings.tone_mapping)
+}
+
+/// Render the scene as seen from `camera` into a linear, unclamped radiance image.
+pub fn render_hdr(scene: &Scene, camera: &Camera, settings: &RenderSettings) -> Rgb32FImage {
+    match &settings.anti_aliasing.adaptive {
+        Some(adaptive) => render_adaptive(scene, camera, settings, adaptive).0,
+        None => render_single_pass(scene, camera, settings),
+    }
+}
+
+/// Render a linear radiance image like [`render_hdr`], also returning the number of
+/// samples each pixel received.
+///
+/// The image is split into tiles that worker threads pick up one at a time. Every pixel
+/// is sampled with its own random sequence, so the result does not depend on the thread
+/// count.
+pub fn render_with_sample_counts(scene: &Scene, camera: &Camera, settings: &RenderSettings) -> (Rgb32FImage, SampleCounts) {
+    match &settings.anti_aliasing.adaptive {
+        Some(adaptive) => render_adaptive(scene, camera, settings, adaptive),
+        None => {
+            let counts = SampleCounts {
+                width: settings.width,
+                height: settings.height,
+                counts: vec![settings.anti_aliasing.samples.max(1); (settings.width * settings.height) as usize],
+            };
+            (render_single_pass(scene, camera, settings), counts)
+        }
+    }
+}
+
+/// Render every pixel with `settings.anti_aliasing.samples` samples, keeping only its
+/// final color.
+fn render_single_pass(scene: &Scene, camera: &Camera, settings: &RenderSettings) -> Rgb32FImage {
+    let (width, height) = (settings.width, settings.height);
+    let tiles = tiles(width, height);
+    let samples = settings.anti_aliasing.samples.max(1);
+
+    let mut colors = vec![Rgb([0.0f32; 3]); (width * height) as usize];
+    for_each_tile(&tiles, &mut colors, settings.thread_count(), |tile, colors| {
+        for ((x, y), output) in tile.pixels().zip(colors) {
+            let mut pixel = Pixel {
+                rng: Rng::for_pixel(x, y),
+                estimate: PixelEstimate::new(),
+            };
+            add_samples(scene, camera, settings, x, y, &mut pixel, samples);
+            let color = pixel.estimate.color();
+            *output = Rgb([color.r as f32, color.g as f32, color.b as f32]);
+        }
+    });
+    ImageBuffer::from_fn(width, height, |x, y| colors[tile_major_index(width, height, x, y)])
+}
+
+/// Render with adaptive sampling, keeping every pixel's sampling state between passes
+/// and refining it in place.
+fn render_adaptive(scene: &Scene, camera: &Camera, settings: &RenderSettings, adaptive: &Adaptive) -> (Rgb32FImage, SampleCounts) {
+    let (width, height) = (settings.width, settings.height);
+    let tiles = tiles(width, height);
+    let threads = settings.thread_count();
+
+    let mut pixels: Vec<Pixel> = tiles
+        .iter()
+        .flat_map(Tile::pixels)
+        .map(|(x, y)| Pixel {
+            rng: Rng::for_pixel(x, y),
+            estimate: PixelEstimate::new(),
+        })
+        .collect();
+    let mut batches = vec![settings.anti_aliasing.samples.max(1); pixels.len()];
+
+    loop {
+        let batches_ref = &batches;
+        for_each_tile(&tiles, &mut pixels, threads, |tile, pixels| {
+            for ((x, y), pixel) in tile.pixels().zip(pixels) {
+                let batch = batches_ref[tile_major_index(width, height, x, y)];
+                if batch > 0 {
+                    add_samples(scene, camera, settings, x, y, pixel, batch);
+                }
+            }
+        });
+
+        let next = refinement(&pixels, &tiles, settings, adaptive);
+        if next.iter().all(|&batch| batch == 0) {
+            break;
+        }
+        batches = next;
+    }
+
+    let pixel = |x: u32, y: u32| &pixels[tile_major_index(width, height, x, y)];
+    let img = ImageBuffer::from_fn(width, height, |x, y| {
+        let color = pixel(x, y).estimate.color();
+        Rgb([color.r as f32, color.g as f32, color.b as f32])
+    });
+    let counts = SampleCounts {
+        width,
+        height,
+        counts: (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| pixel(x, y).estimate.count())
+            .collect(),
+    };
+    (img, counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 1e-9;
+
+    /// Schlick's approximation for a material of index `n` at cosine `cos`.
+    fn schlick(n: f64, cos: f64) -> f64 {
+        let r0 = ((n - 1.0) / (n + 1.0)).powi(2);
+        r0 + (1.0 - r0) * (1.0 - cos).powi(5)
+    }
+
+    #[test]
+    fn refraction_at_normal_incidence_goes_straight_through() {
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+        let (refracted, reflectance) = refract_ray(&normal, &Vec3::new(0.0, -2.0, 0.0), 1.5, true).unwrap();
+        assert!(refracted.sub(&Vec3::new(0.0, -1.0, 0.0)).length() < EPSILON);
+        assert!((reflectance - (0.5f64 / 2.5).powi(2)).abs() < EPSILON);
+    }
+
+    #[test]
+    fn refraction_follows_snells_law() {
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+        let (sin_i, cos_i) = (0.5, 0.75f64.sqrt());
+        let (refracted, reflectance) = refract_ray(&normal, &Vec3::new(sin_i, -cos_i, 0.0), 1.5, true).unwrap();
+        assert!((refracted.length() - 1.0).abs() < EPSILON);
+        assert!((refracted.x - sin_i / 1.5).abs() < EPSILON);
+        assert!((reflectance - schlick(1.5, cos_i)).abs() < EPSILON);
+    }
+
+    #[test]
+    fn leaving_a_material_uses_the_transmitted_angle() {
+        // inside glass, so the normal faces into the material
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+        let (sin_i, cos_i) = (0.5, 0.75f64.sqrt());
+        let (refracted, reflectance) = refract_ray(&normal, &Vec3::new(sin_i, -cos_i, 0.0), 1.5, false).unwrap();
+        let (sin_t, cos_t) = (sin_i * 1.5, (1.0 - 0.75f64.powi(2)).sqrt());
+        assert!((refracted.x - sin_t).abs() < EPSILON);
+        assert!((refracted.y + cos_t).abs() < EPSILON);
+        assert!((reflectance - schlick(1.5, cos_t)).abs() < EPSILON);
+    }
+
+    #[test]
+    fn total_internal_reflection_refracts_nothing() {
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+        // sin 60° * 1.5 > 1
+        let direction = Vec3::new(0.75f64.sqrt(), -0.5, 0.0);
+        assert!(refract_ray(&normal, &direction, 1.5, false).is_none());
+        assert!(refract_ray(&normal, &direction, 1.5, true).is_some());
+    }
+
+    /// A ray from a spotlight pointing down +Z, at `angle` degrees off its axis.
+    fn spot_ray(angle: f64) -> Vec3 {
+        let angle = angle.to_radians();
+        Vec3::new(angle.sin(), 0.0, angle.cos()).scale(3.0)
+    }
+
+    #[test]
+    fn spotlights_are_full_inside_the_inner_cone_and_dark_outside_the_outer_one() {
+        let axis = Vec3::new(0.0, 0.0, 2.0);
+        assert_eq!(spot_falloff(&spot_ray(0.0), &axis, 20.0, 30.0), 1.0);
+        assert_eq!(spot_falloff(&spot_ray(19.0), &axis, 20.0, 30.0), 1.0);
+        assert_eq!(spot_falloff(&spot_ray(31.0), &axis, 20.0, 30.0), 0.0);
+        assert_eq!(spot_falloff(&spot_ray(120.0), &axis, 20.0, 30.0), 0.0);
+    }
+
+    #[test]
+    fn spotlights_fall_off_smoothly_between_the_cones() {
+        let axis = Vec3::new(0.0, 0.0, 1.0);
+        let falloff = [22.0, 25.0, 28.0].map(|angle| spot_falloff(&spot_ray(angle), &axis, 20.0, 30.0));
+        assert!(falloff.iter().all(|&f| f > 0.0 && f < 1.0), "{:?}", falloff);
+        assert!(falloff[0] > falloff[1] && falloff[1] > falloff[2], "{:?}", falloff);
+    }
+
+    #[test]
+    fn spotlights_with_equal_cones_have_a_hard_edge() {
+        let axis = Vec3::new(0.0, 0.0, 1.0);
+        assert_eq!(spot_falloff(&spot_ray(24.0), &axis, 25.0, 25.0), 1.0);
+        assert_eq!(spot_falloff(&spot_ray(26.0), &axis, 25.0, 25.0), 0.0);
+    }
+}
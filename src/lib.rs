@@ -0,0 +1,49 @@
+//! luxst, a 3D ray tracer.
+//!
+//! ```no_run
+//! use luxst::{render, Camera, RenderSettings, Scene, Stage};
+//!
+//! let scene = Scene::basic_scene();
+//! let settings = RenderSettings {
+//!     shading: Stage::Shadows.options(),
+//!     ..RenderSettings::default()
+//! };
+//! let img = render(&scene, &Camera::default(), &settings);
+//! img.save("shadows.png").unwrap();
+//! ```
+
+pub mod bump;
+pub mod bvh;
+pub mod camera;
+pub mod common;
+pub mod hittable;
+pub mod integrator;
+pub mod mesh;
+pub mod noise;
+pub mod obj;
+pub mod output;
+pub mod plane;
+pub mod sampling;
+pub mod scene_file;
+pub mod texture;
+pub mod tonemap;
+
+pub use bump::Bump;
+pub use bvh::{Aabb, Bvh, BvhStats};
+pub use camera::{Camera, FovAxis};
+pub use common::scene::Scene;
+pub use common::{Attenuation, Color, Light, LightType, Material, Sphere, Vec3};
+pub use hittable::{HitRecord, Hittable};
+pub use image;
+pub use integrator::{render, render_hdr, render_with_sample_counts, RenderSettings, ShadingOptions, Stage};
+pub use mesh::{Mesh, MeshTriangle, Triangle};
+pub use noise::{Noise, NoiseBasis};
+pub use output::OutputFormat;
+pub use plane::{Disk, Plane, Quad};
+pub use sampling::{Adaptive, AntiAliasing, Filter, SampleCounts, SamplePattern};
+pub use scene_file::SceneError;
+pub use texture::{
+    ImageTexture, NoisePattern, NoiseTexture, Pattern, PatternTexture, Texture, TextureFilter, TextureMap, TextureSpace,
+    WrapMode,
+};
+pub use tonemap::{ToneMapOperator, ToneMapping, TransferFunction};
@@ -1,40 +1,91 @@
 /*
 run commands, e.g.
-   cargo run --features basic
-   cargo run --features lights
-   cargo run --features reflections
+   cargo run -- render --stage basic --out img/basic.png
+   cargo run -- render --stage reflections --width 1920 --height 1080 --out frame.png
+   cargo run -- render --stage reflections --depth 5
+   cargo run -- render --stage reflections --threads 1
+   cargo run -- render --scene scenes/room.toml --tone-map aces --exposure 1
+   cargo run -- render --stage shadows --out shadows.exr
+   cargo run -- render --stage reflections --samples 16 --filter mitchell
+   cargo run -- render --stage reflections --samples 4 --max-samples 64 --sample-map samples.png
+   cargo run -- render --stage shadows --out - | display
+   cargo run -- render --scene scenes/basic.toml --out scene.png
 */
 
-mod common;
-mod raytracer_basic;
-mod raytracer_v2_lights;
-mod raytracer_v3_specular;
-mod raytracer_v4_shadows;
-mod raytracer_v5_reflections;
+mod cli;
+
+use luxst::{output, RenderSettings, Scene};
+use std::io::{self, Cursor, Write};
+use std::process;
 
 fn main() {
-    #[cfg(feature = "basic")]
-    {
-        raytracer_basic::main();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let command = cli::parse(&args).unwrap_or_else(|err| {
+        eprintln!("error: {}\n\n{}", err, cli::USAGE.lines().next().unwrap_or_default());
+        eprintln!("run 'luxst --help' for the list of options");
+        process::exit(2);
+    });
+
+    match command {
+        cli::Command::Help => println!("{}", cli::USAGE),
+        cli::Command::Render(args) => {
+            if let Err(err) = render(&args) {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        }
     }
+}
 
-    #[cfg(feature = "lights")]
-    {
-        raytracer_v2_lights::main();
+fn render(args: &cli::RenderArgs) -> Result<(), String> {
+    let scene = match args.scene.as_str() {
+        "basic" => Scene::basic_scene(),
+        path => luxst::scene_file::load(path).map_err(|err| err.to_string())?,
+    };
+
+    let mut shading = args.stage.options();
+    if let Some(depth) = args.depth {
+        shading.max_depth = depth;
     }
+    let settings = RenderSettings {
+        width: args.width,
+        height: args.height,
+        shading,
+        threads: args.threads,
+        tone_mapping: args.tone_mapping,
+        anti_aliasing: args.anti_aliasing,
+    };
+
+    eprintln!("bvh: {}", scene.bvh_stats());
 
-    #[cfg(feature = "specular")]
-    {
-        raytracer_v3_specular::main();
+    let mut camera = scene.camera;
+    if let Some(axis) = args.fov_axis {
+        camera.fov_axis = axis;
     }
 
-    #[cfg(feature = "shadows")]
-    {
-        raytracer_v4_shadows::main();
+    let (hdr, sample_counts) = luxst::render_with_sample_counts(&scene, &camera, &settings);
+    if settings.anti_aliasing.adaptive.is_some() {
+        let average = sample_counts.total() as f64 / sample_counts.counts.len() as f64;
+        eprintln!("samples: {:.2} per pixel on average", average);
+    }
+    if let Some(path) = &args.sample_map {
+        sample_counts
+            .to_image()
+            .save(path)
+            .map_err(|err| format!("failed to write '{}': {}", path.display(), err))?;
     }
 
-    #[cfg(feature = "reflections")]
-    {
-        raytracer_v5_reflections::main();
+    if args.out.as_os_str() == "-" {
+        // encoders may need to seek, so buffer the whole image before writing it out
+        let mut buffer = Cursor::new(Vec::new());
+        output::write_to(&mut buffer, &hdr, args.format, &settings.tone_mapping)
+            .map_err(|err| format!("failed to encode image: {}", err))?;
+        io::stdout()
+            .write_all(buffer.get_ref())
+            .map_err(|err| format!("failed to write to stdout: {}", err))
+    } else {
+        output::save(&args.out, &hdr, args.format, &settings.tone_mapping)
+            .map_err(|err| format!("failed to write '{}': {}", args.out.display(), err))
     }
 }
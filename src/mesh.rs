@@ -0,0 +1,261 @@
+//! Triangles and triangle meshes.
+
+use crate::bvh::Aabb;
+use crate::common::{Material, Vec3};
+use crate::hittable::{HitRecord, Hittable};
+use std::sync::Arc;
+
+/// Determinants smaller than this mean the ray is parallel to the triangle.
+const PARALLEL_EPSILON: f64 = 1e-12;
+
+/// Intersect a ray with a triangle using the Möller–Trumbore algorithm.
+///
+/// # Returns
+///
+/// Returns the ray parameter and the barycentric coordinates `(b1, b2)` of the hit with
+/// respect to `v1` and `v2`, if the hit lies in `[t_min, t_max]`.
+pub fn intersect_ray_triangle(
+    origin: &Vec3,
+    direction: &Vec3,
+    [v0, v1, v2]: [&Vec3; 3],
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, f64, f64)> {
+    let edge1 = v1.sub(v0);
+    let edge2 = v2.sub(v0);
+
+    let p = direction.cross(&edge2);
+    let det = edge1.dot(&p);
+    if det.abs() < PARALLEL_EPSILON {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+
+    let s = origin.sub(v0);
+    let b1 = s.dot(&p) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let q = s.cross(&edge1);
+    let b2 = direction.dot(&q) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(&q) * inv_det;
+    (t >= t_min && t <= t_max).then_some((t, b1, b2))
+}
+
+/// Interpolate three per-vertex values with barycentric coordinates.
+fn interpolate([a, b, c]: [&Vec3; 3], b1: f64, b2: f64) -> Vec3 {
+    a.scale(1.0 - b1 - b2).add(&b.scale(b1)).add(&c.scale(b2))
+}
+
+/// Build the hit record for a triangle hit, given as returned by [`intersect_ray_triangle`].
+/// The geometric normal decides which side was hit, while the interpolated vertex normal
+/// (if any) is used for shading. Without texture coordinates the barycentric coordinates
+/// serve as the UV coordinates.
+fn triangle_hit<'a>(
+    (t, b1, b2): (f64, f64, f64),
+    origin: &Vec3,
+    direction: &Vec3,
+    [v0, v1, v2]: [&Vec3; 3],
+    shading_normal: Option<Vec3>,
+    texcoords: Option<[(f64, f64); 3]>,
+    material: &'a Material,
+) -> HitRecord<'a> {
+    let (edge1, edge2) = (v1.sub(v0), v2.sub(v0));
+    let geometric_normal = edge1.cross(&edge2).normalize();
+    let point = origin.add(&direction.scale(t));
+
+    let (uv, (dpdu, dpdv)) = match texcoords {
+        Some([a, b, c]) => {
+            let uv = (
+                a.0 * (1.0 - b1 - b2) + b.0 * b1 + c.0 * b2,
+                a.1 * (1.0 - b1 - b2) + b.1 * b1 + c.1 * b2,
+            );
+            // solve edge = du * dpdu + dv * dpdv for both edges
+            let (du1, dv1, du2, dv2) = (b.0 - a.0, b.1 - a.1, c.0 - a.0, c.1 - a.1);
+            let det = du1 * dv2 - du2 * dv1;
+            let derivatives = if det.abs() < PARALLEL_EPSILON {
+                (edge1, edge2)
+            } else {
+                (
+                    edge1.scale(dv2).sub(&edge2.scale(dv1)).scale(1.0 / det),
+                    edge2.scale(du1).sub(&edge1.scale(du2)).scale(1.0 / det),
+                )
+            };
+            (uv, derivatives)
+        }
+        None => ((b1, b2), (edge1, edge2)),
+    };
+    let mut hit = HitRecord::new(t, point, direction, geometric_normal, material, uv).with_derivatives(dpdu, dpdv);
+
+    if let Some(shading_normal) = shading_normal {
+        hit.normal = if hit.front_face {
+            shading_normal
+        } else {
+            shading_normal.scale(-1.0)
+        };
+    }
+    hit
+}
+
+/// A single triangle, with optional per-vertex normals for smooth shading.
+///
+/// The front side is the one from which the vertices appear counter-clockwise.
+pub struct Triangle {
+    pub vertices: [Vec3; 3],
+    pub normals: Option<[Vec3; 3]>,
+    pub material: Material,
+}
+
+impl Triangle {
+    pub fn new(vertices: [Vec3; 3], material: Material) -> Self {
+        Triangle {
+            vertices,
+            normals: None,
+            material,
+        }
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, origin: &Vec3, direction: &Vec3, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let vertices = self.vertices.each_ref();
+        let hit = intersect_ray_triangle(origin, direction, vertices, t_min, t_max)?;
+        let (_, b1, b2) = hit;
+        Some(triangle_hit(
+            hit,
+            origin,
+            direction,
+            vertices,
+            self.normals
+                .as_ref()
+                .map(|normals| interpolate(normals.each_ref(), b1, b2).normalize()),
+            None,
+            &self.material,
+        ))
+    }
+
+    fn hit_t(&self, origin: &Vec3, direction: &Vec3, t_min: f64, t_max: f64) -> Option<f64> {
+        intersect_ray_triangle(origin, direction, self.vertices.each_ref(), t_min, t_max).map(|(t, _, _)| t)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(&self.vertices))
+    }
+}
+
+/// One triangle of a [`Mesh`], as indices into the mesh's vertex data.
+#[derive(Clone, Copy)]
+pub struct Face {
+    pub positions: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub texcoords: Option<[usize; 3]>,
+    pub material: usize,
+}
+
+/// A triangle mesh with shared vertex data, e.g. loaded from an OBJ file.
+pub struct Mesh {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    pub texcoords: Vec<(f64, f64)>,
+    pub faces: Vec<Face>,
+    pub materials: Vec<Material>,
+}
+
+impl Mesh {
+    /// Move and uniformly scale the mesh: every vertex becomes `offset + scale * vertex`.
+    /// `scale` must be positive, so vertex normals stay valid.
+    pub fn transform(&mut self, offset: &Vec3, scale: f64) {
+        for position in &mut self.positions {
+            *position = offset.add(&position.scale(scale));
+        }
+    }
+
+    /// Intersect a ray with a single face of the mesh.
+    pub fn hit_face(&self, face: &Face, origin: &Vec3, direction: &Vec3, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let vertices = face.positions.map(|i| &self.positions[i]);
+        let hit = intersect_ray_triangle(origin, direction, vertices, t_min, t_max)?;
+        let (_, b1, b2) = hit;
+
+        Some(triangle_hit(
+            hit,
+            origin,
+            direction,
+            vertices,
+            face.normals
+                .map(|normals| interpolate(normals.map(|i| &self.normals[i]), b1, b2).normalize()),
+            face.texcoords.map(|texcoords| texcoords.map(|i| self.texcoords[i])),
+            &self.materials[face.material],
+        ))
+    }
+
+    /// The ray parameter of a hit on a single face, see [`Hittable::hit_t`].
+    pub fn hit_face_t(&self, face: &Face, origin: &Vec3, direction: &Vec3, t_min: f64, t_max: f64) -> Option<f64> {
+        let vertices = face.positions.map(|i| &self.positions[i]);
+        intersect_ray_triangle(origin, direction, vertices, t_min, t_max).map(|(t, _, _)| t)
+    }
+}
+
+impl Hittable for Mesh {
+    fn hit(&self, origin: &Vec3, direction: &Vec3, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mut closest = None;
+        let mut closest_t = t_max;
+
+        for face in &self.faces {
+            if let Some(hit) = self.hit_face(face, origin, direction, t_min, closest_t) {
+                closest_t = hit.t;
+                closest = Some(hit);
+            }
+        }
+
+        closest
+    }
+
+    fn hit_t(&self, origin: &Vec3, direction: &Vec3, t_min: f64, t_max: f64) -> Option<f64> {
+        self.faces.iter().fold(None, |closest, face| {
+            self.hit_face_t(face, origin, direction, t_min, closest.unwrap_or(t_max)).or(closest)
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(&self.positions))
+    }
+}
+
+/// A single face of a shared [`Mesh`], so large meshes can be split up in the scene BVH.
+pub struct MeshTriangle {
+    pub mesh: Arc<Mesh>,
+    pub face: usize,
+}
+
+impl MeshTriangle {
+    /// Split a mesh into one object per face.
+    pub fn from_mesh(mesh: Mesh) -> Vec<MeshTriangle> {
+        let mesh = Arc::new(mesh);
+        (0..mesh.faces.len())
+            .map(|face| MeshTriangle {
+                mesh: Arc::clone(&mesh),
+                face,
+            })
+            .collect()
+    }
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, origin: &Vec3, direction: &Vec3, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.mesh.hit_face(&self.mesh.faces[self.face], origin, direction, t_min, t_max)
+    }
+
+    fn hit_t(&self, origin: &Vec3, direction: &Vec3, t_min: f64, t_max: f64) -> Option<f64> {
+        self.mesh.hit_face_t(&self.mesh.faces[self.face], origin, direction, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let face = &self.mesh.faces[self.face];
+        Some(Aabb::from_points(face.positions.map(|i| &self.mesh.positions[i])))
+    }
+}
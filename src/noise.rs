@@ -0,0 +1,185 @@
+//! Seeded 3D gradient noise and its fractal sums, the basis of natural looking textures.
+
+use crate::common::Vec3;
+use crate::sampling::Rng;
+use std::sync::Arc;
+
+/// The gradient noise function summed by [`Noise`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NoiseBasis {
+    /// Ken Perlin's improved noise, interpolating gradients at the corners of a cubic grid.
+    Perlin,
+    /// Simplex noise, summing gradients at the corners of a tetrahedral grid. Cheaper than
+    /// Perlin noise and without its axis-aligned artifacts.
+    Simplex,
+}
+
+impl NoiseBasis {
+    pub const ALL: [NoiseBasis; 2] = [NoiseBasis::Perlin, NoiseBasis::Simplex];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            NoiseBasis::Perlin => "perlin",
+            NoiseBasis::Simplex => "simplex",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<NoiseBasis> {
+        NoiseBasis::ALL.into_iter().find(|basis| basis.name() == name)
+    }
+}
+
+/// Gradient directions, the midpoints of the edges of a cube.
+const GRADIENTS: [[f64; 3]; 12] = [
+    [1.0, 1.0, 0.0],
+    [-1.0, 1.0, 0.0],
+    [1.0, -1.0, 0.0],
+    [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0],
+    [-1.0, 0.0, 1.0],
+    [1.0, 0.0, -1.0],
+    [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0],
+    [0.0, -1.0, 1.0],
+    [0.0, 1.0, -1.0],
+    [0.0, -1.0, -1.0],
+];
+
+/// A gradient noise generator. The same basis and seed always give the same noise.
+///
+/// The permutation table is shared between clones.
+#[derive(Clone)]
+pub struct Noise {
+    pub basis: NoiseBasis,
+    /// A shuffled permutation of `0..256`, repeated so lookups don't need wrapping.
+    permutation: Arc<[u8; 512]>,
+}
+
+impl Noise {
+    pub fn new(basis: NoiseBasis, seed: u64) -> Self {
+        let mut values: [u8; 256] = std::array::from_fn(|i| i as u8);
+        let mut rng = Rng::new(seed);
+        for i in (1..values.len()).rev() {
+            let j = (rng.next_u64() % (i as u64 + 1)) as usize;
+            values.swap(i, j);
+        }
+        Noise {
+            basis,
+            permutation: Arc::new(std::array::from_fn(|i| values[i % 256])),
+        }
+    }
+
+    /// Noise at `p`, roughly in `[-1, 1]`, varying smoothly over about one unit.
+    pub fn sample(&self, p: &Vec3) -> f64 {
+        match self.basis {
+            NoiseBasis::Perlin => self.perlin(p),
+            NoiseBasis::Simplex => self.simplex(p),
+        }
+    }
+
+    /// Fractal sum of `octaves` layers of noise, each twice the frequency and half the
+    /// amplitude of the previous one, normalized to roughly `[-1, 1]`.
+    pub fn fractal(&self, p: &Vec3, octaves: u32) -> f64 {
+        self.octaves(p, octaves, |noise| noise)
+    }
+
+    /// Like [`Noise::fractal`] but summing the absolute value of every layer, which gives
+    /// billowy shapes with sharp creases. In `[0, 1]`.
+    pub fn turbulence(&self, p: &Vec3, octaves: u32) -> f64 {
+        self.octaves(p, octaves, f64::abs).min(1.0)
+    }
+
+    fn octaves(&self, p: &Vec3, octaves: u32, layer: impl Fn(f64) -> f64) -> f64 {
+        let (mut sum, mut total_amplitude) = (0.0, 0.0);
+        let (mut amplitude, mut frequency) = (1.0, 1.0);
+        for _ in 0..octaves.max(1) {
+            sum += amplitude * layer(self.sample(&p.scale(frequency)));
+            total_amplitude += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.0;
+        }
+        sum / total_amplitude
+    }
+
+    fn hash(&self, i: usize) -> usize {
+        self.permutation[i] as usize
+    }
+
+    fn perlin(&self, p: &Vec3) -> f64 {
+        let (xf, yf, zf) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let cell = |f: f64| (f as i64 & 255) as usize;
+        let (xi, yi, zi) = (cell(xf), cell(yf), cell(zf));
+        let (x, y, z) = (p.x - xf, p.y - yf, p.z - zf);
+        let fade = |t: f64| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+        let lerp = |t: f64, a: f64, b: f64| a + t * (b - a);
+
+        // dot product of the corner's gradient with the offset from the corner
+        let corner = |dx: usize, dy: usize, dz: usize| {
+            let h = self.hash(self.hash(self.hash(xi + dx) + yi + dy) + zi + dz);
+            let [gx, gy, gz] = GRADIENTS[h % 12];
+            gx * (x - dx as f64) + gy * (y - dy as f64) + gz * (z - dz as f64)
+        };
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 0), corner(1, 0, 0)),
+                lerp(u, corner(0, 1, 0), corner(1, 1, 0)),
+            ),
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 1), corner(1, 0, 1)),
+                lerp(u, corner(0, 1, 1), corner(1, 1, 1)),
+            ),
+        )
+    }
+
+    fn simplex(&self, p: &Vec3) -> f64 {
+        const SKEW: f64 = 1.0 / 3.0;
+        const UNSKEW: f64 = 1.0 / 6.0;
+
+        // find the tetrahedron containing the point in the skewed cubic grid
+        let s = (p.x + p.y + p.z) * SKEW;
+        let (i, j, k) = ((p.x + s).floor(), (p.y + s).floor(), (p.z + s).floor());
+        let t = (i + j + k) * UNSKEW;
+        let d0 = [p.x - (i - t), p.y - (j - t), p.z - (k - t)];
+
+        // offsets of the second and third corners, stepping along the largest coordinates
+        let [x, y, z] = d0;
+        let (step1, step2) = if x >= y {
+            if y >= z {
+                ([1, 0, 0], [1, 1, 0])
+            } else if x >= z {
+                ([1, 0, 0], [1, 0, 1])
+            } else {
+                ([0, 0, 1], [1, 0, 1])
+            }
+        } else if y < z {
+            ([0, 0, 1], [0, 1, 1])
+        } else if x < z {
+            ([0, 1, 0], [0, 1, 1])
+        } else {
+            ([0, 1, 0], [1, 1, 0])
+        };
+
+        let cell = |f: f64| (f as i64 & 255) as usize;
+        let (ii, jj, kk) = (cell(i), cell(j), cell(k));
+        let corners = [[0, 0, 0], step1, step2, [1, 1, 1]];
+        corners
+            .iter()
+            .enumerate()
+            .map(|(n, offset)| {
+                let d = [0, 1, 2].map(|axis| d0[axis] - offset[axis] as f64 + n as f64 * UNSKEW);
+                let falloff = 0.6 - d[0] * d[0] - d[1] * d[1] - d[2] * d[2];
+                if falloff < 0.0 {
+                    return 0.0;
+                }
+                let h = self.hash(ii + offset[0] + self.hash(jj + offset[1] + self.hash(kk + offset[2])));
+                let [gx, gy, gz] = GRADIENTS[h % 12];
+                falloff.powi(4) * (gx * d[0] + gy * d[1] + gz * d[2])
+            })
+            .sum::<f64>()
+            * 32.0
+    }
+}
@@ -0,0 +1,331 @@
+//! Wavefront OBJ/MTL import.
+//!
+//! Supports vertex positions, texture coordinates and normals, polygonal faces (split
+//! into triangle fans) and materials. MTL properties map onto [`Material`] as follows:
+//!
+//! * `Kd` - diffuse color, in linear RGB
+//! * `Ns` - specular exponent, a value of 0 disables highlights
+//! * `Ks` with `illum` 3 to 9 (the models with reflections) - reflectivity, the mean of
+//!   the `Ks` channels
+//! * `Pm` - reflectivity (the metallic term of the PBR extension), overriding `Ks`
+//! * `d` - dissolve, the opacity; `Tr` is its complement
+//! * `Ni` - index of refraction
+
+use crate::common::{Color, Material, Vec3};
+use crate::mesh::{Face, Mesh};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// An error encountered while loading an OBJ or MTL file.
+#[derive(Debug)]
+pub enum ObjError {
+    /// A file could not be read.
+    Io { path: PathBuf, source: std::io::Error },
+    /// A line could not be parsed. `line` is 1-based.
+    Invalid { path: PathBuf, line: usize, message: String },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            ObjError::Invalid { path, line, message } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+/// Load a mesh from an OBJ file. Faces without a `usemtl` material use `default_material`.
+pub fn load(path: impl AsRef<Path>, default_material: Material) -> Result<Mesh, ObjError> {
+    let path = path.as_ref();
+    let source = read(path)?;
+    let invalid = |line: usize, message: String| ObjError::Invalid {
+        path: path.to_path_buf(),
+        line,
+        message,
+    };
+
+    let mut mesh = Mesh {
+        positions: Vec::new(),
+        normals: Vec::new(),
+        texcoords: Vec::new(),
+        faces: Vec::new(),
+        materials: vec![default_material],
+    };
+    let mut material_names: HashMap<String, usize> = HashMap::new();
+    let mut current_material = 0;
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => {
+                let v = parse_floats(&args, 3).map_err(|message| invalid(line_number, message))?;
+                mesh.positions.push(Vec3::new(v[0], v[1], v[2]));
+            }
+            "vn" => {
+                let n = parse_floats(&args, 3).map_err(|message| invalid(line_number, message))?;
+                mesh.normals.push(Vec3::new(n[0], n[1], n[2]).normalize());
+            }
+            "vt" => {
+                let uv = parse_floats(&args, 1).map_err(|message| invalid(line_number, message))?;
+                mesh.texcoords.push((uv[0], uv.get(1).copied().unwrap_or(0.0)));
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(invalid(line_number, "a face needs at least 3 vertices".to_string()));
+                }
+                let vertices = args
+                    .iter()
+                    .map(|vertex| parse_face_vertex(vertex, &mesh))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|message| invalid(line_number, message))?;
+
+                // split polygons into a fan of triangles around the first vertex
+                for i in 1..vertices.len() - 1 {
+                    let corners = [vertices[0], vertices[i], vertices[i + 1]];
+                    mesh.faces.push(Face {
+                        positions: corners.map(|(position, _, _)| position),
+                        texcoords: all_some(corners.map(|(_, texcoord, _)| texcoord)),
+                        normals: all_some(corners.map(|(_, _, normal)| normal)),
+                        material: current_material,
+                    });
+                }
+            }
+            "mtllib" => {
+                let directory = path.parent().unwrap_or(Path::new(""));
+                for file in &args {
+                    for (name, material) in load_mtl(&directory.join(file))? {
+                        material_names.insert(name, mesh.materials.len());
+                        mesh.materials.push(material);
+                    }
+                }
+            }
+            "usemtl" => {
+                let name = args.join(" ");
+                current_material = *material_names
+                    .get(&name)
+                    .ok_or_else(|| invalid(line_number, format!("unknown material '{}'", name)))?;
+            }
+            // groups, objects, smoothing groups and anything else don't affect rendering
+            _ => {}
+        }
+    }
+
+    Ok(mesh)
+}
+
+/// Illumination models (`illum`) with reflections, from ray traced or not.
+const REFLECTIVE_ILLUM: std::ops::RangeInclusive<u32> = 3..=9;
+
+/// The keys of a material that decide its reflectivity, see the module docs.
+#[derive(Default)]
+struct ReflectionKeys {
+    ks: Option<f64>,
+    illum: Option<u32>,
+    pm: bool,
+}
+
+/// Load the materials of an MTL file, in file order.
+fn load_mtl(path: &Path) -> Result<Vec<(String, Material)>, ObjError> {
+    let source = read(path)?;
+    let invalid = |line: usize, message: String| ObjError::Invalid {
+        path: path.to_path_buf(),
+        line,
+        message,
+    };
+
+    let mut materials: Vec<(String, Material)> = Vec::new();
+    let mut reflection_keys: Vec<ReflectionKeys> = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            materials.push((
+                args.join(" "),
+                Material {
+                    color: Color::new(0.8, 0.8, 0.8), // MTL default Kd
+                    ..Material::default()
+                },
+            ));
+            reflection_keys.push(ReflectionKeys::default());
+            continue;
+        }
+
+        let (Some((_, material)), Some(keys)) = (materials.last_mut(), reflection_keys.last_mut()) else {
+            if ["Kd", "Ks", "Ns", "illum", "Pm", "d", "Tr", "Ni"].contains(&keyword) {
+                return Err(invalid(line_number, format!("'{}' before any 'newmtl'", keyword)));
+            }
+            continue;
+        };
+        match keyword {
+            "Kd" => {
+                let rgb = parse_floats(&args, 3).map_err(|message| invalid(line_number, message))?;
+                let channel = |c: f64| c.clamp(0.0, 1.0);
+                material.color = Color::new(channel(rgb[0]), channel(rgb[1]), channel(rgb[2]));
+            }
+            "Ks" => {
+                let rgb = parse_floats(&args, 3).map_err(|message| invalid(line_number, message))?;
+                keys.ks = Some((rgb[0] + rgb[1] + rgb[2]) / 3.0);
+            }
+            "Ns" => {
+                let ns = parse_floats(&args, 1).map_err(|message| invalid(line_number, message))?[0];
+                material.specular = (ns > 0.0).then_some(ns);
+            }
+            "illum" => {
+                let illum = args.first().and_then(|arg| arg.parse().ok());
+                let message = || invalid(line_number, "expected an illumination model number".to_string());
+                keys.illum = Some(illum.ok_or_else(message)?);
+            }
+            "Pm" => {
+                let pm = parse_floats(&args, 1).map_err(|message| invalid(line_number, message))?[0];
+                material.reflective = (pm > 0.0).then_some(pm.min(1.0));
+                keys.pm = true;
+            }
+            "d" | "Tr" => {
+                let value = parse_floats(&args, 1).map_err(|message| invalid(line_number, message))?[0];
+                let transparency = if keyword == "d" { 1.0 - value } else { value }.clamp(0.0, 1.0);
+                material.transparency = (transparency > 0.0).then_some(transparency);
+            }
+            "Ni" => {
+                let ni = parse_floats(&args, 1).map_err(|message| invalid(line_number, message))?[0];
+                if ni <= 0.0 {
+                    return Err(invalid(line_number, "index of refraction must be positive".to_string()));
+                }
+                material.refractive_index = ni;
+            }
+            _ => {}
+        }
+    }
+
+    // `Ks` and `illum` may come in either order, so they are resolved at the end
+    for ((_, material), keys) in materials.iter_mut().zip(reflection_keys) {
+        if !keys.pm && keys.illum.is_some_and(|illum| REFLECTIVE_ILLUM.contains(&illum)) {
+            material.reflective = keys.ks.filter(|&ks| ks > 0.0).map(|ks| ks.min(1.0));
+        }
+    }
+
+    Ok(materials)
+}
+
+fn read(path: &Path) -> Result<String, ObjError> {
+    std::fs::read_to_string(path).map_err(|source| ObjError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Parse the arguments as numbers, requiring at least `count` of them. Extra values are
+/// kept, e.g. the optional `w` of `v`.
+fn parse_floats(args: &[&str], count: usize) -> Result<Vec<f64>, String> {
+    if args.len() < count {
+        return Err(format!("expected {} numbers, found {}", count, args.len()));
+    }
+    args.iter()
+        .map(|arg| arg.parse().map_err(|_| format!("invalid number '{}'", arg)))
+        .collect()
+}
+
+/// Parse a face vertex of the form `v`, `v/vt`, `v//vn` or `v/vt/vn` into 0-based indices.
+fn parse_face_vertex(vertex: &str, mesh: &Mesh) -> Result<(usize, Option<usize>, Option<usize>), String> {
+    let mut parts = vertex.split('/');
+    let position = resolve_index(parts.next(), mesh.positions.len(), vertex)?
+        .ok_or_else(|| format!("face vertex '{}' has no position", vertex))?;
+    let texcoord = resolve_index(parts.next(), mesh.texcoords.len(), vertex)?;
+    let normal = resolve_index(parts.next(), mesh.normals.len(), vertex)?;
+    Ok((position, texcoord, normal))
+}
+
+/// Resolve a 1-based (or negative, relative to the end) OBJ index. Empty parts are `None`.
+fn resolve_index(part: Option<&str>, len: usize, vertex: &str) -> Result<Option<usize>, String> {
+    let Some(part) = part.filter(|part| !part.is_empty()) else {
+        return Ok(None);
+    };
+    let index: i64 = part
+        .parse()
+        .map_err(|_| format!("invalid index '{}' in face vertex '{}'", part, vertex))?;
+    let resolved = if index > 0 { index - 1 } else { len as i64 + index };
+    if index == 0 || resolved < 0 || resolved >= len as i64 {
+        return Err(format!("index {} in face vertex '{}' is out of range", index, vertex));
+    }
+    Ok(Some(resolved as usize))
+}
+
+fn all_some(values: [Option<usize>; 3]) -> Option<[usize; 3]> {
+    match values {
+        [Some(a), Some(b), Some(c)] => Some([a, b, c]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_positive_and_negative_indices() {
+        assert_eq!(resolve_index(Some("1"), 3, "1"), Ok(Some(0)));
+        assert_eq!(resolve_index(Some("3"), 3, "3"), Ok(Some(2)));
+        assert_eq!(resolve_index(Some("-1"), 3, "-1"), Ok(Some(2)));
+        assert_eq!(resolve_index(Some("-3"), 3, "-3"), Ok(Some(0)));
+    }
+
+    #[test]
+    fn missing_parts_are_none() {
+        assert_eq!(resolve_index(None, 3, "1"), Ok(None));
+        assert_eq!(resolve_index(Some(""), 3, "1//2"), Ok(None));
+    }
+
+    #[test]
+    fn rejects_out_of_range_and_invalid_indices() {
+        for index in ["0", "4", "-4"] {
+            let err = resolve_index(Some(index), 3, index).unwrap_err();
+            assert!(err.contains("out of range"), "{}", err);
+        }
+        assert!(resolve_index(Some("-1"), 0, "-1").is_err());
+        let err = resolve_index(Some("x"), 3, "1/x").unwrap_err();
+        assert_eq!(err, "invalid index 'x' in face vertex '1/x'");
+    }
+
+    /// Load the materials of `source`, written to a temporary MTL file.
+    fn materials(name: &str, source: &str) -> Vec<(String, Material)> {
+        let path = std::env::temp_dir().join(format!("luxst-obj-{}-{}.mtl", std::process::id(), name));
+        std::fs::write(&path, source).unwrap();
+        let materials = load_mtl(&path);
+        std::fs::remove_file(&path).unwrap();
+        materials.unwrap()
+    }
+
+    #[test]
+    fn reflectivity_comes_from_ks_with_a_reflective_illum() {
+        let source = "newmtl mirror\nillum 3\nKs 0.2 0.3 0.4\n\
+                      newmtl plastic\nKs 0.5 0.5 0.5\nillum 2\n\
+                      newmtl dark\nKs 0 0 0\nillum 3\n\
+                      newmtl metal\nKs 0.5 0.5 0.5\nillum 3\nPm 0.8\n";
+        let reflective: Vec<_> = materials("ks", source).iter().map(|(_, m)| m.reflective).collect();
+        assert_eq!(reflective.len(), 4);
+        assert!((reflective[0].unwrap() - 0.3).abs() < 1e-12);
+        assert_eq!(reflective[1..], [None, None, Some(0.8)]);
+    }
+
+    #[test]
+    fn rejects_reflection_keys_before_newmtl() {
+        let path = std::env::temp_dir().join(format!("luxst-obj-{}-orphan.mtl", std::process::id()));
+        std::fs::write(&path, "illum 3\nnewmtl late\n").unwrap();
+        let result = load_mtl(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,186 @@
+//! Writing rendered images in the supported output formats.
+//!
+//! Integer formats store tone mapped, display encoded colors. The floating point formats
+//! (Radiance HDR and OpenEXR) store linear radiance, only scaled by the exposure, so they
+//! can be tone mapped later, e.g. during compositing.
+
+use crate::common::Color;
+use crate::tonemap::{tone_map, ToneMapping};
+use image::codecs::hdr::HdrEncoder;
+use image::codecs::pnm::{PnmSubtype, SampleEncoding};
+use image::{DynamicImage, ImageBuffer, ImageFormat, ImageOutputFormat, ImageResult, Rgb, Rgb32FImage};
+use std::io::{Seek, Write};
+use std::path::Path;
+
+/// File format of a rendered image.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OutputFormat {
+    /// 8-bit PNG.
+    Png,
+    /// 16-bit PNG.
+    Png16,
+    /// Binary 8-bit PPM (`P6`).
+    Ppm,
+    /// Radiance RGBE, linear.
+    Hdr,
+    /// OpenEXR with 32-bit float channels, linear.
+    Exr,
+    /// Any other 8-bit format the `image` crate can write, e.g. BMP or TGA.
+    Other(ImageFormat),
+}
+
+impl OutputFormat {
+    /// Formats that can be selected by name.
+    pub const NAMED: [OutputFormat; 5] = [
+        OutputFormat::Png,
+        OutputFormat::Png16,
+        OutputFormat::Ppm,
+        OutputFormat::Hdr,
+        OutputFormat::Exr,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Png16 => "png16",
+            OutputFormat::Ppm => "ppm",
+            OutputFormat::Hdr => "hdr",
+            OutputFormat::Exr => "exr",
+            OutputFormat::Other(format) => format.extensions_str().first().copied().unwrap_or("other"),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<OutputFormat> {
+        OutputFormat::NAMED.into_iter().find(|format| format.name() == name)
+    }
+
+    /// Pick the format from a file extension. `.png` selects 8-bit PNG.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<OutputFormat> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Some(OutputFormat::Png),
+            "ppm" => Some(OutputFormat::Ppm),
+            "hdr" => Some(OutputFormat::Hdr),
+            "exr" => Some(OutputFormat::Exr),
+            _ => ImageFormat::from_extension(&extension)
+                .filter(|format| format.can_write())
+                .map(OutputFormat::Other),
+        }
+    }
+
+    /// Usual file extension for the format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Png16 => "png",
+            format => format.name(),
+        }
+    }
+
+    /// Whether the format stores linear floating point radiance.
+    pub fn is_hdr(&self) -> bool {
+        matches!(self, OutputFormat::Hdr | OutputFormat::Exr)
+    }
+}
+
+/// Encode a linear radiance image (see [`render_hdr`](crate::render_hdr)) in `format`.
+///
+/// Integer formats are converted with `tone_mapping`; floating point formats only use its
+/// exposure.
+pub fn write_to<W: Write + Seek>(
+    writer: &mut W,
+    hdr: &Rgb32FImage,
+    format: OutputFormat,
+    tone_mapping: &ToneMapping,
+) -> ImageResult<()> {
+    match format {
+        OutputFormat::Png => DynamicImage::ImageRgb8(tone_map(hdr, tone_mapping)).write_to(writer, ImageOutputFormat::Png),
+        OutputFormat::Png16 => {
+            let img = ImageBuffer::from_fn(hdr.width(), hdr.height(), |x, y| {
+                let mapped = tone_mapping.map(&pixel_color(hdr.get_pixel(x, y)));
+                let channel = |c: f64| (c * 65535.0).round() as u16;
+                Rgb([channel(mapped.r), channel(mapped.g), channel(mapped.b)])
+            });
+            DynamicImage::ImageRgb16(img).write_to(writer, ImageOutputFormat::Png)
+        }
+        OutputFormat::Ppm => DynamicImage::ImageRgb8(tone_map(hdr, tone_mapping))
+            .write_to(writer, ImageOutputFormat::Pnm(PnmSubtype::Pixmap(SampleEncoding::Binary))),
+        OutputFormat::Hdr => {
+            let pixels: Vec<Rgb<f32>> = exposed(hdr, tone_mapping).pixels().copied().collect();
+            HdrEncoder::new(writer).encode(&pixels, hdr.width() as usize, hdr.height() as usize)
+        }
+        OutputFormat::Exr => DynamicImage::ImageRgb32F(exposed(hdr, tone_mapping)).write_to(writer, ImageOutputFormat::OpenExr),
+        OutputFormat::Other(format) => DynamicImage::ImageRgb8(tone_map(hdr, tone_mapping)).write_to(writer, format),
+    }
+}
+
+/// Write a linear radiance image to `path` in `format`, see [`write_to`].
+pub fn save(path: impl AsRef<Path>, hdr: &Rgb32FImage, format: OutputFormat, tone_mapping: &ToneMapping) -> ImageResult<()> {
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    write_to(&mut file, hdr, format, tone_mapping)?;
+    file.flush()?;
+    Ok(())
+}
+
+fn pixel_color(pixel: &Rgb<f32>) -> Color {
+    let [r, g, b] = pixel.0;
+    Color::new(r as f64, g as f64, b as f64)
+}
+
+/// The radiance image scaled by the exposure of `tone_mapping`.
+fn exposed(hdr: &Rgb32FImage, tone_mapping: &ToneMapping) -> Rgb32FImage {
+    let scale = tone_mapping.exposure.exp2() as f32;
+    let mut img = hdr.clone();
+    for pixel in img.pixels_mut() {
+        pixel.0 = pixel.0.map(|c| c * scale);
+    }
+    img
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn formats_follow_the_file_extension() {
+        let cases = [
+            ("out.png", OutputFormat::Png),
+            ("out.PNG", OutputFormat::Png),
+            ("dir/out.ppm", OutputFormat::Ppm),
+            ("out.hdr", OutputFormat::Hdr),
+            ("out.exr", OutputFormat::Exr),
+            ("out.bmp", OutputFormat::Other(ImageFormat::Bmp)),
+        ];
+        for (path, format) in cases {
+            assert_eq!(OutputFormat::from_path(path), Some(format), "{}", path);
+        }
+        for path in ["out.txt", "out", "out.png.gz"] {
+            assert_eq!(OutputFormat::from_path(path), None, "{}", path);
+        }
+    }
+
+    #[test]
+    fn formats_round_trip_through_their_names() {
+        for format in OutputFormat::NAMED {
+            assert_eq!(OutputFormat::from_name(format.name()), Some(format));
+        }
+        assert_eq!(OutputFormat::Png16.extension(), "png");
+        assert_eq!(OutputFormat::from_name("tiff"), None);
+    }
+
+    #[test]
+    fn writes_the_format_signature() {
+        let hdr = ImageBuffer::from_fn(3, 2, |x, y| Rgb([x as f32, y as f32, 2.5]));
+        let cases: [(OutputFormat, &[u8]); 4] = [
+            (OutputFormat::Png, b"\x89PNG"),
+            (OutputFormat::Ppm, b"P6"),
+            (OutputFormat::Hdr, b"#?RADIANCE"),
+            (OutputFormat::Exr, b"\x76\x2f\x31\x01"),
+        ];
+        for (format, magic) in cases {
+            let mut bytes = Cursor::new(Vec::new());
+            write_to(&mut bytes, &hdr, format, &ToneMapping::default()).unwrap();
+            assert!(bytes.get_ref().starts_with(magic), "{}", format.name());
+        }
+    }
+}
@@ -0,0 +1,258 @@
+//! Flat primitives: infinite planes and the bounded disk and quad.
+
+use crate::bvh::Aabb;
+use crate::common::{geometry, Material, Vec3};
+use crate::hittable::{HitRecord, Hittable};
+use std::f64::consts::PI;
+
+/// Rays closer than this to parallel with a plane are treated as missing it.
+const PARALLEL_EPSILON: f64 = 1e-12;
+
+/// Intersect a ray with the plane through `point` with normal `normal`.
+///
+/// # Returns
+///
+/// Returns the ray parameter of the hit if it lies in `[t_min, t_max]`.
+fn intersect_ray_plane(origin: &Vec3, direction: &Vec3, point: &Vec3, normal: &Vec3, t_min: f64, t_max: f64) -> Option<f64> {
+    let denom = normal.dot(direction);
+    if denom.abs() < PARALLEL_EPSILON {
+        return None;
+    }
+    let t = point.sub(origin).dot(normal) / denom;
+    (t >= t_min && t <= t_max).then_some(t)
+}
+
+/// An infinite plane, e.g. a floor or a wall.
+pub struct Plane {
+    pub point: Vec3,
+    /// Unit normal of the front side.
+    pub normal: Vec3,
+    pub material: Material,
+}
+
+impl Plane {
+    pub fn new(point: Vec3, normal: Vec3, material: Material) -> Self {
+        Plane {
+            point,
+            normal: normal.normalize(),
+            material,
+        }
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, origin: &Vec3, direction: &Vec3, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let t = intersect_ray_plane(origin, direction, &self.point, &self.normal, t_min, t_max)?;
+        let point = origin.add(&direction.scale(t));
+
+        // tile the plane with unit squares along its tangent axes
+        let (tangent, bitangent) = geometry::orthonormal_basis(&self.normal);
+        let local = point.sub(&self.point);
+        let uv = (local.dot(&tangent).rem_euclid(1.0), local.dot(&bitangent).rem_euclid(1.0));
+
+        Some(HitRecord::new(t, point, direction, self.normal, &self.material, uv).with_derivatives(tangent, bitangent))
+    }
+
+    fn hit_t(&self, origin: &Vec3, direction: &Vec3, t_min: f64, t_max: f64) -> Option<f64> {
+        intersect_ray_plane(origin, direction, &self.point, &self.normal, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+}
+
+/// A circular disk, a plane bounded by `radius` around `center`.
+pub struct Disk {
+    pub center: Vec3,
+    /// Unit normal of the front side.
+    pub normal: Vec3,
+    pub radius: f64,
+    pub material: Material,
+}
+
+impl Disk {
+    pub fn new(center: Vec3, normal: Vec3, radius: f64, material: Material) -> Self {
+        Disk {
+            center,
+            normal: normal.normalize(),
+            radius,
+            material,
+        }
+    }
+
+    /// The ray parameter and the point of a hit within the disk, relative to its center.
+    fn intersect(&self, origin: &Vec3, direction: &Vec3, t_min: f64, t_max: f64) -> Option<(f64, Vec3)> {
+        let t = intersect_ray_plane(origin, direction, &self.center, &self.normal, t_min, t_max)?;
+        let local = origin.add(&direction.scale(t)).sub(&self.center);
+        (local.dot(&local) <= self.radius * self.radius).then_some((t, local))
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, origin: &Vec3, direction: &Vec3, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (t, local) = self.intersect(origin, direction, t_min, t_max)?;
+        let point = self.center.add(&local);
+        let distance = local.length();
+
+        // polar coordinates: angle around the normal, then distance from the center
+        let (tangent, bitangent) = geometry::orthonormal_basis(&self.normal);
+        let (x, y) = (local.dot(&tangent), local.dot(&bitangent));
+        let angle = y.atan2(x) + PI;
+        let uv = (angle / (2.0 * PI), distance / self.radius);
+        let dpdu = bitangent.scale(x).sub(&tangent.scale(y)).scale(2.0 * PI);
+        // the radial direction is undefined at the center itself, any will do
+        let radial = if distance > 0.0 { local.scale(1.0 / distance) } else { tangent };
+        let dpdv = radial.scale(self.radius);
+
+        Some(HitRecord::new(t, point, direction, self.normal, &self.material, uv).with_derivatives(dpdu, dpdv))
+    }
+
+    fn hit_t(&self, origin: &Vec3, direction: &Vec3, t_min: f64, t_max: f64) -> Option<f64> {
+        self.intersect(origin, direction, t_min, t_max).map(|(t, _)| t)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // extent of the disk along each axis is radius * sin(angle between normal and axis)
+        let n = &self.normal;
+        let extent = |c: f64| self.radius * (1.0 - c * c).max(0.0).sqrt();
+        let e = Vec3::new(extent(n.x), extent(n.y), extent(n.z));
+        Some(Aabb::new(self.center.sub(&e), self.center.add(&e)))
+    }
+}
+
+/// A parallelogram spanned by two edges from a corner.
+pub struct Quad {
+    pub corner: Vec3,
+    pub edge_u: Vec3,
+    pub edge_v: Vec3,
+    pub material: Material,
+}
+
+impl Quad {
+    pub fn new(corner: Vec3, edge_u: Vec3, edge_v: Vec3, material: Material) -> Self {
+        Quad {
+            corner,
+            edge_u,
+            edge_v,
+            material,
+        }
+    }
+
+    /// Unit normal of the front side, following the right-hand rule from `edge_u` to `edge_v`.
+    pub fn normal(&self) -> Vec3 {
+        self.edge_u.cross(&self.edge_v).normalize()
+    }
+
+    /// The ray parameter and the planar coordinates `(a, b)` of a hit within the quad, at
+    /// `corner + a * edge_u + b * edge_v`.
+    fn intersect(&self, origin: &Vec3, direction: &Vec3, t_min: f64, t_max: f64) -> Option<(f64, f64, f64)> {
+        let n = self.edge_u.cross(&self.edge_v);
+        let t = intersect_ray_plane(origin, direction, &self.corner, &n.normalize(), t_min, t_max)?;
+
+        // solve point = corner + a * edge_u + b * edge_v for the planar coordinates
+        let local = origin.add(&direction.scale(t)).sub(&self.corner);
+        let w = n.scale(1.0 / n.dot(&n));
+        let a = w.dot(&local.cross(&self.edge_v));
+        let b = w.dot(&self.edge_u.cross(&local));
+        ((0.0..=1.0).contains(&a) && (0.0..=1.0).contains(&b)).then_some((t, a, b))
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, origin: &Vec3, direction: &Vec3, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (t, a, b) = self.intersect(origin, direction, t_min, t_max)?;
+        let point = origin.add(&direction.scale(t));
+        Some(HitRecord::new(t, point, direction, self.normal(), &self.material, (a, b)).with_derivatives(self.edge_u, self.edge_v))
+    }
+
+    fn hit_t(&self, origin: &Vec3, direction: &Vec3, t_min: f64, t_max: f64) -> Option<f64> {
+        self.intersect(origin, direction, t_min, t_max).map(|(t, _, _)| t)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let far = self.corner.add(&self.edge_u).add(&self.edge_v);
+        Some(Aabb::from_points([
+            &self.corner,
+            &self.corner.add(&self.edge_u),
+            &self.corner.add(&self.edge_v),
+            &far,
+        ]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 1e-9;
+
+    fn is_finite(v: &Vec3) -> bool {
+        v.x.is_finite() && v.y.is_finite() && v.z.is_finite()
+    }
+
+    /// A ray from `origin` straight down the -Y axis.
+    fn down_from(x: f64, z: f64) -> (Vec3, Vec3) {
+        (Vec3::new(x, 5.0, z), Vec3::new(0.0, -1.0, 0.0))
+    }
+
+    #[test]
+    fn planes_are_hit_from_both_sides_but_not_by_parallel_rays() {
+        let plane = Plane::new(Vec3::new(0.0, -1.0, 0.0), Vec3::new(0.0, 2.0, 0.0), Material::default());
+        let (origin, direction) = down_from(100.0, -40.0);
+        let hit = plane.hit(&origin, &direction, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 6.0).abs() < EPSILON);
+        assert!(hit.front_face);
+
+        let below = plane.hit(&Vec3::new(0.0, -3.0, 0.0), &Vec3::new(0.0, 1.0, 0.0), 0.001, f64::INFINITY).unwrap();
+        assert!(!below.front_face);
+        assert!((below.normal.y + 1.0).abs() < EPSILON);
+
+        assert!(plane.hit(&origin, &direction, 0.001, 5.0).is_none());
+        assert!(plane.hit(&origin, &Vec3::new(1.0, 0.0, 0.0), 0.001, f64::INFINITY).is_none());
+        assert!(plane.bounding_box().is_none());
+    }
+
+    #[test]
+    fn disks_are_hit_within_their_radius() {
+        let disk = Disk::new(Vec3::new(1.0, 0.0, 2.0), Vec3::new(0.0, 1.0, 0.0), 2.0, Material::default());
+        let (origin, direction) = down_from(2.5, 2.0);
+        let hit = disk.hit(&origin, &direction, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 5.0).abs() < EPSILON);
+        assert!((hit.uv.1 - 0.75).abs() < EPSILON);
+
+        let (origin, direction) = down_from(3.5, 2.0);
+        assert!(disk.hit(&origin, &direction, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn disks_have_finite_derivatives_at_their_center() {
+        let disk = Disk::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 1.0, Material::default());
+        let (origin, direction) = down_from(0.0, 0.0);
+        let hit = disk.hit(&origin, &direction, 0.001, f64::INFINITY).unwrap();
+        let (dpdu, dpdv) = hit.derivatives();
+        assert!(is_finite(&dpdu) && is_finite(&dpdv));
+        assert!((dpdv.length() - 1.0).abs() < EPSILON);
+        assert!(dpdv.dot(&disk.normal).abs() < EPSILON);
+    }
+
+    #[test]
+    fn quads_are_hit_within_their_edges() {
+        let quad = Quad::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 1.0),
+            Material::default(),
+        );
+        let (origin, direction) = down_from(1.5, 0.5);
+        let hit = quad.hit(&origin, &direction, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 5.0).abs() < EPSILON);
+        assert!((hit.uv.0 - 0.5).abs() < EPSILON && (hit.uv.1 - 0.5).abs() < EPSILON);
+
+        // outside the parallelogram, the first two within its bounding box
+        for (x, z) in [(0.2, 0.8), (2.8, 0.2), (-0.1, 0.0), (1.0, 1.1)] {
+            let (origin, direction) = down_from(x, z);
+            assert!(quad.hit(&origin, &direction, 0.001, f64::INFINITY).is_none(), "({}, {})", x, z);
+        }
+    }
+}
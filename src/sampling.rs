@@ -0,0 +1,403 @@
+//! Sub-pixel sample placement and pixel reconstruction filters for anti-aliasing.
+
+use crate::common::{Color, Vec3};
+use image::{ImageBuffer, Luma, RgbImage};
+
+/// A small, fast pseudo-random generator (SplitMix64).
+///
+/// Renders seed one generator per pixel, so results don't depend on the order in which
+/// pixels are rendered.
+#[derive(Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    /// A generator for pixel `(x, y)`, independent of those of other pixels.
+    pub fn for_pixel(x: u32, y: u32) -> Self {
+        let mut rng = Rng::new(((y as u64) << 32) | x as u64);
+        rng.next_u64();
+        rng
+    }
+
+    /// A generator for shading the surface point `p`, so random choices made there (e.g.
+    /// for soft shadows) don't depend on the order in which points are shaded.
+    pub fn for_point(p: &Vec3) -> Self {
+        let seed = p.x.to_bits() ^ p.y.to_bits().rotate_left(21) ^ p.z.to_bits().rotate_left(42);
+        let mut rng = Rng::new(seed);
+        rng.next_u64();
+        rng
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A uniformly distributed number in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        // the top 53 bits fill the mantissa exactly
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// How samples are distributed within a pixel.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SamplePattern {
+    /// Independent uniformly random positions.
+    Jittered,
+    /// One random position in each cell of a grid of equal-area cells, which avoids
+    /// clumping.
+    Stratified,
+}
+
+impl SamplePattern {
+    pub const ALL: [SamplePattern; 2] = [SamplePattern::Jittered, SamplePattern::Stratified];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SamplePattern::Jittered => "jittered",
+            SamplePattern::Stratified => "stratified",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<SamplePattern> {
+        SamplePattern::ALL.into_iter().find(|pattern| pattern.name() == name)
+    }
+
+    /// Position of sample `index` of `count` in the unit square.
+    pub fn sample(&self, index: u32, count: u32, rng: &mut Rng) -> (f64, f64) {
+        match self {
+            SamplePattern::Jittered => (rng.next_f64(), rng.next_f64()),
+            SamplePattern::Stratified => {
+                // split the square into rows of cells; rows with more cells are taller, so
+                // every cell covers 1 / count of the area
+                let rows = (count as f64).sqrt().round().max(1.0) as u32;
+                let (base, extra) = (count / rows, count % rows);
+                let cells_in = |row: u32| base + u32::from(row < extra);
+
+                let (mut row, mut first) = (0, 0);
+                while index >= first + cells_in(row) {
+                    first += cells_in(row);
+                    row += 1;
+                }
+                let cells = cells_in(row);
+                let column = index - first;
+
+                let row_start = first as f64 / count as f64;
+                let row_height = cells as f64 / count as f64;
+                (
+                    (column as f64 + rng.next_f64()) / cells as f64,
+                    row_start + rng.next_f64() * row_height,
+                )
+            }
+        }
+    }
+}
+
+/// Reconstruction filter weighting the samples of a pixel by their distance from its
+/// center.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Filter {
+    /// Equal weights over the pixel.
+    Box,
+    /// Weights falling off linearly, one pixel wide on each side.
+    Tent,
+    /// A truncated Gaussian with a standard deviation of half a pixel.
+    Gaussian,
+    /// The Mitchell-Netravali cubic with B = C = 1/3, sharper than the Gaussian.
+    Mitchell,
+}
+
+impl Filter {
+    pub const ALL: [Filter; 4] = [Filter::Box, Filter::Tent, Filter::Gaussian, Filter::Mitchell];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Filter::Box => "box",
+            Filter::Tent => "tent",
+            Filter::Gaussian => "gaussian",
+            Filter::Mitchell => "mitchell",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Filter> {
+        Filter::ALL.into_iter().find(|filter| filter.name() == name)
+    }
+
+    /// Half the width of the filter's support, in pixels.
+    pub fn radius(&self) -> f64 {
+        match self {
+            Filter::Box => 0.5,
+            Filter::Tent => 1.0,
+            Filter::Gaussian => 1.5,
+            Filter::Mitchell => 2.0,
+        }
+    }
+
+    /// The fewest samples per pixel that cover the filter's support, one per pixel of it.
+    pub fn min_samples(&self) -> u32 {
+        let width = (2.0 * self.radius()).ceil() as u32;
+        width * width
+    }
+
+    /// Weight of a sample at offset `(dx, dy)` pixels from the pixel center, 1 at the
+    /// center.
+    pub fn weight(&self, dx: f64, dy: f64) -> f64 {
+        self.weight_1d(dx) * self.weight_1d(dy)
+    }
+
+    fn weight_1d(&self, d: f64) -> f64 {
+        let d = d.abs();
+        match self {
+            Filter::Box => 1.0,
+            Filter::Tent => (1.0 - d).max(0.0),
+            Filter::Gaussian => {
+                let sigma: f64 = 0.5;
+                let gaussian = |d: f64| (-d * d / (2.0 * sigma * sigma)).exp();
+                // shift down so the weight reaches zero at the edge of the support, and
+                // rescale to keep 1 at the center
+                let edge = gaussian(self.radius());
+                ((gaussian(d) - edge) / (1.0 - edge)).max(0.0)
+            }
+            Filter::Mitchell => {
+                let (b, c) = (1.0 / 3.0, 1.0 / 3.0);
+                let value = if d < 1.0 {
+                    (12.0 - 9.0 * b - 6.0 * c) * d * d * d + (-18.0 + 12.0 * b + 6.0 * c) * d * d + (6.0 - 2.0 * b)
+                } else if d < 2.0 {
+                    (-b - 6.0 * c) * d * d * d
+                        + (6.0 * b + 30.0 * c) * d * d
+                        + (-12.0 * b - 48.0 * c) * d
+                        + (8.0 * b + 24.0 * c)
+                } else {
+                    0.0
+                };
+                value / (6.0 - 2.0 * b)
+            }
+        }
+    }
+}
+
+/// Anti-aliasing settings: how many samples each pixel gets, where they go and how they
+/// are combined.
+///
+/// Samples are spread over the filter's support around the pixel center, so with wide
+/// filters a pixel also sees part of its neighbors. That takes at least
+/// [`Filter::min_samples`] per pixel; pixels with fewer keep their samples within the
+/// pixel, which the command line rejects. A pixel's first sample is placed at its center
+/// when it gets only one initially.
+#[derive(Clone, Copy, Debug)]
+pub struct AntiAliasing {
+    /// Samples per pixel, or the size of each batch in adaptive mode.
+    pub samples: u32,
+    pub pattern: SamplePattern,
+    pub filter: Filter,
+    /// Refine noisy or high-contrast pixels with more samples.
+    pub adaptive: Option<Adaptive>,
+}
+
+impl Default for AntiAliasing {
+    fn default() -> Self {
+        AntiAliasing {
+            samples: 1,
+            pattern: SamplePattern::Stratified,
+            filter: Filter::Box,
+            adaptive: None,
+        }
+    }
+}
+
+/// Adaptive sampling: after the initial batch of [`AntiAliasing::samples`], pixels keep
+/// getting further batches while their estimate looks noisy or they differ strongly
+/// from a neighbor.
+#[derive(Clone, Copy, Debug)]
+pub struct Adaptive {
+    /// No pixel gets more samples than this.
+    pub max_samples: u32,
+    /// Refine while the standard error of the pixel's luminance is above this.
+    pub noise_threshold: f64,
+    /// Refine while any color channel of the pixel differs by more than this from a
+    /// horizontal or vertical neighbor, comparing values clamped to `[0, 1]`.
+    pub contrast_threshold: f64,
+}
+
+impl Default for Adaptive {
+    fn default() -> Self {
+        Adaptive {
+            max_samples: 64,
+            noise_threshold: 0.01,
+            contrast_threshold: 0.1,
+        }
+    }
+}
+
+/// Relative luminance of a linear color, clamped to the displayable range so a few very
+/// bright samples don't dominate the sampling decisions.
+fn display_luminance(color: &Color) -> f64 {
+    color.luminance().clamp(0.0, 1.0)
+}
+
+/// Below this mean filter weight per sample a pixel's estimate falls back to the plain
+/// mean of its samples.
+const MIN_MEAN_WEIGHT: f64 = 0.01;
+
+/// Running filtered estimate of a pixel's color, with the statistics adaptive sampling
+/// needs.
+#[derive(Clone)]
+pub struct PixelEstimate {
+    weighted: Color,
+    total_weight: f64,
+    unweighted: Color,
+    count: u32,
+    luminance_sum: f64,
+    luminance_sq_sum: f64,
+}
+
+impl PixelEstimate {
+    pub fn new() -> Self {
+        PixelEstimate {
+            weighted: Color::new(0.0, 0.0, 0.0),
+            total_weight: 0.0,
+            unweighted: Color::new(0.0, 0.0, 0.0),
+            count: 0,
+            luminance_sum: 0.0,
+            luminance_sq_sum: 0.0,
+        }
+    }
+
+    /// Add a sample with its filter weight.
+    pub fn add(&mut self, color: &Color, weight: f64) {
+        self.weighted = self.weighted.add(&color.scale(weight));
+        self.total_weight += weight;
+        self.unweighted = self.unweighted.add(color);
+        self.count += 1;
+        let luminance = display_luminance(color);
+        self.luminance_sum += luminance;
+        self.luminance_sq_sum += luminance * luminance;
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// The filtered color of the samples so far.
+    pub fn color(&self) -> Color {
+        if self.count <= 1 {
+            return self.unweighted;
+        }
+        if self.total_weight <= MIN_MEAN_WEIGHT * self.count as f64 {
+            // the negative lobes of the Mitchell filter can (nearly) cancel out the positive
+            // weights, and dividing by what's left would blow the color up
+            return self.unweighted.scale(1.0 / self.count as f64);
+        }
+        let color = self.weighted.scale(1.0 / self.total_weight);
+        // negative lobes can also overshoot below zero next to bright edges
+        Color::new(color.r.max(0.0), color.g.max(0.0), color.b.max(0.0))
+    }
+
+    /// The largest difference between the two estimates in any color channel, with
+    /// channels clamped to `[0, 1]`.
+    pub fn contrast(&self, other: &PixelEstimate) -> f64 {
+        let (a, b) = (self.color(), other.color());
+        let channel = |x: f64, y: f64| (x.clamp(0.0, 1.0) - y.clamp(0.0, 1.0)).abs();
+        channel(a.r, b.r).max(channel(a.g, b.g)).max(channel(a.b, b.b))
+    }
+
+    /// Standard error of the mean sample luminance, 0 with fewer than two samples.
+    pub fn standard_error(&self) -> f64 {
+        if self.count < 2 {
+            return 0.0;
+        }
+        let n = self.count as f64;
+        let variance = (self.luminance_sq_sum - self.luminance_sum * self.luminance_sum / n) / (n - 1.0);
+        (variance.max(0.0) / n).sqrt()
+    }
+}
+
+impl Default for PixelEstimate {
+    fn default() -> Self {
+        PixelEstimate::new()
+    }
+}
+
+/// Number of samples taken for every pixel of a render, in row-major order.
+pub struct SampleCounts {
+    pub width: u32,
+    pub height: u32,
+    pub counts: Vec<u32>,
+}
+
+impl SampleCounts {
+    pub fn get(&self, x: u32, y: u32) -> u32 {
+        self.counts[(y * self.width + x) as usize]
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.iter().map(|&count| count as u64).sum()
+    }
+
+    /// Debug image of the counts: black for no samples, white for the most samples of
+    /// any pixel.
+    pub fn to_image(&self) -> RgbImage {
+        let max = self.counts.iter().copied().max().unwrap_or(0).max(1);
+        let gray: ImageBuffer<Luma<u8>, Vec<u8>> = ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            Luma([(self.get(x, y) as f64 / max as f64 * 255.0).round() as u8])
+        });
+        image::DynamicImage::ImageLuma8(gray).to_rgb8()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_weigh_one_at_the_center_and_zero_at_the_edge() {
+        for filter in Filter::ALL {
+            let (center, edge) = (filter.weight(0.0, 0.0), filter.weight(filter.radius(), 0.0));
+            assert!((center - 1.0).abs() < 1e-12, "{}: {}", filter.name(), center);
+            if filter != Filter::Box {
+                assert!(edge.abs() < 1e-12, "{}: {}", filter.name(), edge);
+                assert_eq!(filter.weight(0.0, filter.radius() + 0.1), 0.0, "{}", filter.name());
+            }
+        }
+    }
+
+    #[test]
+    fn min_samples_cover_the_support() {
+        let min_samples = Filter::ALL.map(|filter| filter.min_samples());
+        assert_eq!(min_samples, [1, 4, 9, 16]);
+    }
+
+    #[test]
+    fn stratified_samples_fill_every_cell_once() {
+        // cells per row, rows of the unit square from the top
+        for (count, rows) in [(1, vec![1]), (4, vec![2, 2]), (5, vec![3, 2]), (7, vec![3, 2, 2])] {
+            for seed in 0..20 {
+                let mut rng = Rng::new(seed);
+                let mut cells = Vec::new();
+                for index in 0..count {
+                    let (u, v) = SamplePattern::Stratified.sample(index, count, &mut rng);
+                    assert!((0.0..1.0).contains(&u) && (0.0..1.0).contains(&v));
+                    // rows with more cells are taller, so every cell covers 1 / count
+                    let (mut row, mut row_start) = (0, 0.0);
+                    while v >= row_start + rows[row] as f64 / count as f64 {
+                        row_start += rows[row] as f64 / count as f64;
+                        row += 1;
+                    }
+                    cells.push((row, (u * rows[row] as f64) as u32));
+                }
+                cells.sort();
+                cells.dedup();
+                assert_eq!(cells.len(), count as usize, "count {}, seed {}", count, seed);
+            }
+        }
+    }
+}
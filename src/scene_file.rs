@@ -0,0 +1,999 @@
+//! Loading scenes from TOML scene description files.
+//!
+//! A scene file looks like this (every top-level key is optional):
+//!
+//! ```toml
+//! background = [0, 0, 0]   # colors are 8-bit sRGB
+//!
+//! [camera]
+//! position = [0.0, 1.0, -2.0]
+//! target = [0.0, 0.0, 3.0]   # default: one unit down +Z from `position`
+//! up = [0.0, 1.0, 0.0]       # default
+//! fov = 60.0                 # in degrees
+//! fov_axis = "vertical"      # default, or "horizontal"
+//!
+//! [materials.red]
+//! color = [255, 0, 0]
+//! specular = 500.0
+//! reflective = 0.2
+//! transparency = 0.0        # fraction of light refracted through the surface
+//! refractive_index = 1.0    # e.g. 1.5 for glass
+//!
+//! [materials.floor]
+//! color = [255, 255, 0]
+//! # replaces `color`; pattern is checker, stripes, rings or gradient
+//! texture = { pattern = "checker", colors = [[255, 255, 0], [40, 40, 40]], scale = 1.0, space = "world" }
+//!
+//! [materials.marble]
+//! color = [255, 255, 255]
+//! specular = 300.0
+//! # noise is noise, turbulence, marble, wood or clouds; basis is perlin or simplex
+//! texture = { noise = "marble", colors = [[60, 60, 70], [240, 240, 235]], scale = 2.0, basis = "perlin", octaves = 6, seed = 0 }
+//! # maps vary specular or reflective by a texture's luminance, between the `range` values
+//! reflective_map = { noise = "clouds", range = [0.0, 0.3], seed = 1 }
+//!
+//! [materials.stucco]
+//! color = [230, 220, 200]
+//! # shade as if displaced along the normal by heights (in world units) from a texture map
+//! bump = { noise = "turbulence", scale = 20.0, range = [0.0, 0.01] }
+//!
+//! [materials.bricks]
+//! color = [180, 80, 60]
+//! # or tangent-space normals from an image, in the OpenGL convention
+//! normal_map = { image = "bricks_normal.png", filter = "bilinear", wrap = "repeat", scale = 1.0, strength = 1.0 }
+//!
+//! [materials.earth]
+//! color = [255, 255, 255]
+//! # an image mapped by the surface UV coordinates, relative to the scene file
+//! texture = { image = "earth.png", filter = "bilinear", wrap = "repeat", scale = 1.0 }
+//!
+//! [[spheres]]
+//! center = [0.0, -1.0, 3.0]
+//! radius = 1.0
+//! material = "red"
+//!
+//! [[planes]]                # infinite, e.g. floors and walls
+//! point = [0.0, -1.0, 0.0]
+//! normal = [0.0, 1.0, 0.0]
+//! material = "red"
+//!
+//! [[disks]]
+//! center = [0.0, 3.0, 5.0]
+//! normal = [0.0, 0.0, -1.0]
+//! radius = 1.0
+//! material = "red"
+//!
+//! [[quads]]                 # parallelogram spanned by `u` and `v` from `corner`
+//! corner = [-1.0, -1.0, 6.0]
+//! u = [2.0, 0.0, 0.0]
+//! v = [0.0, 2.0, 0.0]
+//! material = "red"
+//!
+//! [[triangles]]             # front side is where the vertices are counter-clockwise
+//! vertices = [[-1.0, 0.0, 5.0], [1.0, 0.0, 5.0], [0.0, 1.5, 5.0]]
+//! material = "red"
+//!
+//! [[meshes]]                # Wavefront OBJ, relative to the scene file
+//! file = "teapot.obj"
+//! material = "red"          # optional, for faces without an MTL material
+//! position = [0.0, -1.0, 3.0]
+//! scale = 0.5
+//!
+//! [[lights]]
+//! type = "ambient"
+//! intensity = 0.2
+//!
+//! [[lights]]
+//! type = "point"
+//! position = [2.0, 1.0, 0.0]
+//! intensity = 0.6
+//! color = [255, 255, 255]
+//! attenuation = "none"      # default; linear, inverse_square, or a table of coefficients
+//!                           # of 1 / (constant + linear * d + quadratic * d²), e.g.
+//!                           # { constant = 1.0, linear = 0.1, quadratic = 0.02 }
+//! range = 20.0              # optional, points this far away aren't lit (or shadow tested)
+//!
+//! [[lights]]
+//! type = "spot"
+//! position = [0.0, 3.0, 1.0]
+//! direction = [0.0, -1.0, 1.0]
+//! inner_angle = 15.0        # full intensity within this angle of `direction`, in degrees
+//! outer_angle = 25.0        # fading out up to this angle
+//! intensity = 0.8
+//!
+//! [[lights]]
+//! type = "spherical"        # area lights cast soft shadows
+//! center = [0.0, 3.0, 2.0]
+//! radius = 0.5
+//! intensity = 0.6
+//! samples = 16              # shadow rays per shaded point, default 16
+//!
+//! [[lights]]
+//! type = "rectangular"      # parallelogram spanned by `u` and `v` from `corner`
+//! corner = [-1.0, 3.0, 2.0]
+//! u = [2.0, 0.0, 0.0]
+//! v = [0.0, 0.0, 1.0]
+//! intensity = 0.6
+//! ```
+
+use crate::bump::Bump;
+use crate::camera::{Camera, FovAxis};
+use crate::common::{scene::Scene, Attenuation, Color, Light, Material, Sphere, Vec3};
+use crate::noise::{Noise, NoiseBasis};
+use crate::texture::{
+    ImageTexture, NoisePattern, NoiseTexture, Pattern, PatternTexture, Texture, TextureFilter, TextureMap, TextureSpace,
+    WrapMode,
+};
+use crate::hittable::Hittable;
+use crate::mesh::{Mesh, MeshTriangle, Triangle};
+use crate::obj;
+use crate::plane::{Disk, Plane, Quad};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use toml::Spanned;
+
+/// An error encountered while loading a scene file.
+#[derive(Debug)]
+pub enum SceneError {
+    /// The file could not be read.
+    Io { path: PathBuf, source: std::io::Error },
+    /// The file is not a valid scene description. `line` and `column` are 1-based.
+    Invalid {
+        path: Option<PathBuf>,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            SceneError::Invalid { path, line, column, message } => {
+                if let Some(path) = path {
+                    write!(f, "{}:", path.display())?;
+                }
+                write!(f, "{}:{}: {}", line, column, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+/// Read and parse the scene file at `path`.
+pub fn load(path: impl AsRef<Path>) -> Result<Scene, SceneError> {
+    let path = path.as_ref();
+    let source = std::fs::read_to_string(path).map_err(|source| SceneError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+    parse_in(&source, base_dir).map_err(|err| match err {
+        SceneError::Invalid { line, column, message, .. } => SceneError::Invalid {
+            path: Some(path.to_path_buf()),
+            line,
+            column,
+            message,
+        },
+        err => err,
+    })
+}
+
+/// Parse a scene description from TOML source text. Relative paths, e.g. of meshes, are
+/// resolved against the current directory.
+pub fn parse(source: &str) -> Result<Scene, SceneError> {
+    parse_in(source, Path::new(""))
+}
+
+fn parse_in(source: &str, base_dir: &Path) -> Result<Scene, SceneError> {
+    let file: SceneFile = toml::from_str(source).map_err(|err| {
+        let offset = err.span().map(|span| span.start).unwrap_or(0);
+        invalid(source, offset, err.message().to_string())
+    })?;
+    Builder { source, base_dir }.build(file)
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneFile {
+    background: Option<[u8; 3]>,
+    camera: Option<Spanned<CameraDesc>>,
+    #[serde(default)]
+    materials: BTreeMap<String, MaterialDesc>,
+    #[serde(default)]
+    spheres: Vec<Spanned<SphereDesc>>,
+    #[serde(default)]
+    planes: Vec<PlaneDesc>,
+    #[serde(default)]
+    disks: Vec<DiskDesc>,
+    #[serde(default)]
+    quads: Vec<Spanned<QuadDesc>>,
+    #[serde(default)]
+    triangles: Vec<Spanned<TriangleDesc>>,
+    #[serde(default)]
+    meshes: Vec<MeshDesc>,
+    #[serde(default)]
+    lights: Vec<Spanned<LightDesc>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDesc {
+    position: [f64; 3],
+    target: Option<[f64; 3]>,
+    up: Option<[f64; 3]>,
+    fov: Option<Spanned<f64>>,
+    fov_axis: Option<Spanned<String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MaterialDesc {
+    color: [u8; 3],
+    specular: Option<Spanned<f64>>,
+    reflective: Option<Spanned<f64>>,
+    transparency: Option<Spanned<f64>>,
+    refractive_index: Option<Spanned<f64>>,
+    texture: Option<Spanned<TextureDesc>>,
+    specular_map: Option<Spanned<TextureDesc>>,
+    reflective_map: Option<Spanned<TextureDesc>>,
+    /// Texture map of heights along the normal, in world units.
+    bump: Option<Spanned<TextureDesc>>,
+    normal_map: Option<Spanned<NormalMapDesc>>,
+}
+
+/// One of a procedural `pattern`, a `noise` pattern or an `image`, with the options that
+/// apply to it.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TextureDesc {
+    pattern: Option<Spanned<String>>,
+    noise: Option<Spanned<String>>,
+    image: Option<Spanned<String>>,
+    /// Pattern and noise colors, default black and white.
+    colors: Option<Spanned<[[u8; 3]; 2]>>,
+    /// `world` (default) or `uv`.
+    space: Option<Spanned<String>>,
+    /// `perlin` (default) or `simplex`.
+    basis: Option<Spanned<String>>,
+    octaves: Option<Spanned<u32>>,
+    seed: Option<Spanned<u64>>,
+    /// `bilinear` (default) or `nearest`.
+    filter: Option<Spanned<String>>,
+    /// `repeat` (default) or `clamp`.
+    wrap: Option<Spanned<String>>,
+    /// Repetitions per unit, default 1.
+    scale: Option<Spanned<f64>>,
+    /// Values at texture values 0 and 1, required by texture maps.
+    range: Option<Spanned<[f64; 2]>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NormalMapDesc {
+    /// Image file, relative to the scene file.
+    image: Spanned<String>,
+    /// `bilinear` (default) or `nearest`.
+    filter: Option<Spanned<String>>,
+    /// `repeat` (default) or `clamp`.
+    wrap: Option<Spanned<String>>,
+    /// Repetitions per unit, default 1.
+    scale: Option<Spanned<f64>>,
+    /// Scales the tilt of the normals, default 1.
+    strength: Option<Spanned<f64>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SphereDesc {
+    center: [f64; 3],
+    radius: Spanned<f64>,
+    material: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PlaneDesc {
+    point: [f64; 3],
+    normal: Spanned<[f64; 3]>,
+    material: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DiskDesc {
+    center: [f64; 3],
+    normal: Spanned<[f64; 3]>,
+    radius: Spanned<f64>,
+    material: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct QuadDesc {
+    corner: [f64; 3],
+    u: [f64; 3],
+    v: [f64; 3],
+    material: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TriangleDesc {
+    vertices: [[f64; 3]; 3],
+    material: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MeshDesc {
+    file: Spanned<String>,
+    material: Option<Spanned<String>>,
+    position: Option<[f64; 3]>,
+    scale: Option<Spanned<f64>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LightDesc {
+    #[serde(rename = "type")]
+    kind: Spanned<String>,
+    intensity: Spanned<f64>,
+    color: Option<[u8; 3]>,
+    position: Option<[f64; 3]>,
+    direction: Option<Spanned<[f64; 3]>>,
+    /// Point light falloff with distance, `none` by default.
+    attenuation: Option<Spanned<AttenuationDesc>>,
+    /// Distance beyond which a point light is skipped.
+    range: Option<Spanned<f64>>,
+    /// Spotlight cone half-angles in degrees.
+    inner_angle: Option<Spanned<f64>>,
+    outer_angle: Option<Spanned<f64>>,
+    center: Option<[f64; 3]>,
+    radius: Option<Spanned<f64>>,
+    corner: Option<[f64; 3]>,
+    u: Option<[f64; 3]>,
+    v: Option<[f64; 3]>,
+    /// Shadow rays per shaded point for area lights, default 16.
+    samples: Option<Spanned<u32>>,
+}
+
+/// Either the name of a falloff (`none`, `linear` or `inverse_square`) or the
+/// coefficients of `1 / (constant + linear * d + quadratic * d²)`.
+#[derive(Deserialize)]
+#[serde(
+    untagged,
+    expecting = "an attenuation name or a table of `constant`, `linear` and `quadratic` coefficients"
+)]
+enum AttenuationDesc {
+    Name(String),
+    Coefficients(CoefficientsDesc),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CoefficientsDesc {
+    /// Default 1.
+    constant: Option<f64>,
+    /// Default 0.
+    linear: Option<f64>,
+    /// Default 0.
+    quadratic: Option<f64>,
+}
+
+/// Converts the deserialized description into a [`Scene`], validating values against
+/// the source text so errors can point at the offending line.
+struct Builder<'a> {
+    source: &'a str,
+    /// Directory that relative paths in the scene are resolved against.
+    base_dir: &'a Path,
+}
+
+impl Builder<'_> {
+    fn build(&self, file: SceneFile) -> Result<Scene, SceneError> {
+        let mut materials = BTreeMap::new();
+        for (name, desc) in &file.materials {
+            materials.insert(name.as_str(), self.material(desc)?);
+        }
+
+        let mut objects: Vec<Box<dyn Hittable>> = Vec::new();
+        for sphere in &file.spheres {
+            objects.push(Box::new(self.sphere(sphere, &materials)?));
+        }
+        for plane in &file.planes {
+            let normal = self.direction(&plane.normal, "normal")?;
+            let material = self.lookup_material(&plane.material, &materials)?;
+            objects.push(Box::new(Plane::new(vec3(plane.point), normal, material)));
+        }
+        for disk in &file.disks {
+            self.check(&disk.radius, *disk.radius.get_ref() > 0.0, "radius must be positive")?;
+            let normal = self.direction(&disk.normal, "normal")?;
+            let material = self.lookup_material(&disk.material, &materials)?;
+            objects.push(Box::new(Disk::new(vec3(disk.center), normal, *disk.radius.get_ref(), material)));
+        }
+        for quad in &file.quads {
+            let (u, v) = (vec3(quad.get_ref().u), vec3(quad.get_ref().v));
+            if u.cross(&v).length() == 0.0 {
+                return Err(self.error(quad.span(), "quad edges `u` and `v` must not be parallel".to_string()));
+            }
+            let material = self.lookup_material(&quad.get_ref().material, &materials)?;
+            objects.push(Box::new(Quad::new(vec3(quad.get_ref().corner), u, v, material)));
+        }
+        for triangle in &file.triangles {
+            let vertices = triangle.get_ref().vertices.map(vec3);
+            let [v0, v1, v2] = &vertices;
+            if v1.sub(v0).cross(&v2.sub(v0)).length() == 0.0 {
+                return Err(self.error(triangle.span(), "triangle vertices must not be collinear".to_string()));
+            }
+            let material = self.lookup_material(&triangle.get_ref().material, &materials)?;
+            objects.push(Box::new(Triangle::new(vertices, material)));
+        }
+        for mesh in &file.meshes {
+            let mesh = self.mesh(mesh, &materials)?;
+            for triangle in MeshTriangle::from_mesh(mesh) {
+                objects.push(Box::new(triangle));
+            }
+        }
+        let lights = file
+            .lights
+            .iter()
+            .map(|light| self.light(light))
+            .collect::<Result<_, _>>()?;
+
+        let mut scene = Scene::new(objects, lights);
+        scene.background = file.background.map(color);
+        if let Some(camera) = &file.camera {
+            scene.camera = self.camera(camera)?;
+        }
+        Ok(scene)
+    }
+
+    fn camera(&self, desc: &Spanned<CameraDesc>) -> Result<Camera, SceneError> {
+        let span = desc.span();
+        let desc = desc.get_ref();
+
+        let mut camera = Camera::default();
+        camera.position = vec3(desc.position);
+        camera.target = desc.target.map(vec3).unwrap_or(camera.position.add(&Vec3::new(0.0, 0.0, 1.0)));
+        if let Some(up) = desc.up {
+            camera.up = vec3(up);
+        }
+        if let Some(axis) = &desc.fov_axis {
+            camera.fov_axis = FovAxis::from_name(axis.get_ref()).ok_or_else(|| {
+                self.error(axis.span(), "fov_axis must be \"vertical\" or \"horizontal\"".to_string())
+            })?;
+        }
+        if let Some(fov) = &desc.fov {
+            let degrees = *fov.get_ref();
+            self.check(fov, degrees > 0.0 && degrees < 180.0, "fov must be between 0 and 180 degrees")?;
+            camera.set_fov(degrees);
+        }
+
+        let forward = camera.target.sub(&camera.position);
+        if forward.length() == 0.0 {
+            return Err(self.error(span, "camera `target` must differ from `position`".to_string()));
+        }
+        if camera.up.cross(&forward).length() == 0.0 {
+            return Err(self.error(span, "camera `up` must not be parallel to the viewing direction".to_string()));
+        }
+        Ok(camera)
+    }
+
+    fn material(&self, desc: &MaterialDesc) -> Result<Material, SceneError> {
+        if let Some(specular) = &desc.specular {
+            self.check(specular, *specular.get_ref() >= 0.0, "specular exponent must not be negative")?;
+        }
+        if let Some(reflective) = &desc.reflective {
+            let r = *reflective.get_ref();
+            self.check(reflective, (0.0..=1.0).contains(&r), "reflective must be between 0 and 1")?;
+        }
+        if let Some(transparency) = &desc.transparency {
+            let t = *transparency.get_ref();
+            self.check(transparency, (0.0..=1.0).contains(&t), "transparency must be between 0 and 1")?;
+        }
+        if let Some(index) = &desc.refractive_index {
+            self.check(index, *index.get_ref() > 0.0, "refractive_index must be positive")?;
+        }
+        let texture = match &desc.texture {
+            Some(texture) => {
+                self.reject(&texture.get_ref().range, "`range` only applies to texture maps")?;
+                Some(self.texture(texture)?)
+            }
+            None => None,
+        };
+        let specular_map = desc
+            .specular_map
+            .as_ref()
+            .map(|map| self.texture_map(map, |s| s >= 0.0, "specular exponents must not be negative"))
+            .transpose()?;
+        let reflective_map = desc
+            .reflective_map
+            .as_ref()
+            .map(|map| self.texture_map(map, |r| (0.0..=1.0).contains(&r), "reflectivity must be between 0 and 1"))
+            .transpose()?;
+        let bump = match (&desc.bump, &desc.normal_map) {
+            (Some(_), Some(normal_map)) => {
+                return Err(self.error(
+                    normal_map.span(),
+                    "a material can't have both `bump` and `normal_map`".to_string(),
+                ))
+            }
+            (Some(bump), None) => Some(Bump::Height(self.texture_map(bump, f64::is_finite, "heights must be finite")?)),
+            (None, Some(normal_map)) => Some(self.normal_map(normal_map.get_ref())?),
+            (None, None) => None,
+        };
+        Ok(Material {
+            color: color(desc.color),
+            texture,
+            specular: desc.specular.as_ref().map(|s| *s.get_ref()),
+            specular_map,
+            reflective: desc.reflective.as_ref().map(|r| *r.get_ref()),
+            reflective_map,
+            transparency: desc.transparency.as_ref().map(|t| *t.get_ref()),
+            refractive_index: desc.refractive_index.as_ref().map_or(1.0, |n| *n.get_ref()),
+            bump,
+        })
+    }
+
+    fn texture(&self, desc: &Spanned<TextureDesc>) -> Result<Texture, SceneError> {
+        let span = desc.span();
+        let desc = desc.get_ref();
+        let scale = desc.scale.as_ref().map_or(1.0, |scale| *scale.get_ref());
+        if let Some(spanned) = &desc.scale {
+            self.check(spanned, scale > 0.0, "scale must be positive")?;
+        }
+        match (&desc.pattern, &desc.noise, &desc.image) {
+            (Some(pattern), None, None) => self.pattern_texture(pattern, desc, scale),
+            (None, Some(noise), None) => self.noise_texture(noise, desc, scale),
+            (None, None, Some(image)) => self.image_texture(image, desc, scale),
+            _ => Err(self.error(
+                span,
+                "texture needs exactly one of `pattern`, `noise` or `image`".to_string(),
+            )),
+        }
+    }
+
+    /// A texture driving a material property, whose `range` must satisfy `valid`.
+    fn texture_map(
+        &self,
+        desc: &Spanned<TextureDesc>,
+        valid: impl Fn(f64) -> bool,
+        message: &str,
+    ) -> Result<TextureMap, SceneError> {
+        let range = desc
+            .get_ref()
+            .range
+            .as_ref()
+            .ok_or_else(|| self.error(desc.span(), "texture map needs a `range`".to_string()))?;
+        self.check(range, range.get_ref().iter().all(|&value| valid(value)), message)?;
+        Ok(TextureMap::new(self.texture(desc)?, *range.get_ref()))
+    }
+
+    fn pattern_texture(&self, pattern: &Spanned<String>, desc: &TextureDesc, scale: f64) -> Result<Texture, SceneError> {
+        self.reject_options(desc, &["basis", "octaves", "seed", "filter", "wrap"], "pattern")?;
+        let pattern = self.named(
+            pattern,
+            Pattern::from_name,
+            "pattern",
+            "checker, stripes, rings or gradient",
+        )?;
+        Ok(Texture::Pattern(PatternTexture::new(
+            pattern,
+            self.texture_colors(desc),
+            scale,
+            self.texture_space(desc)?,
+        )))
+    }
+
+    fn noise_texture(&self, noise: &Spanned<String>, desc: &TextureDesc, scale: f64) -> Result<Texture, SceneError> {
+        self.reject_options(desc, &["filter", "wrap"], "noise")?;
+        let pattern = self.named(
+            noise,
+            NoisePattern::from_name,
+            "noise pattern",
+            "noise, turbulence, marble, wood or clouds",
+        )?;
+        let basis = match &desc.basis {
+            Some(name) => self.named(name, NoiseBasis::from_name, "noise basis", "perlin or simplex")?,
+            None => NoiseBasis::Perlin,
+        };
+        let seed = desc.seed.as_ref().map_or(0, |seed| *seed.get_ref());
+        let mut texture = NoiseTexture::new(
+            Noise::new(basis, seed),
+            pattern,
+            self.texture_colors(desc),
+            scale,
+            self.texture_space(desc)?,
+        );
+        if let Some(octaves) = &desc.octaves {
+            self.check(octaves, (1..=16).contains(octaves.get_ref()), "octaves must be between 1 and 16")?;
+            texture.octaves = *octaves.get_ref();
+        }
+        Ok(Texture::Noise(texture))
+    }
+
+    fn image_texture(&self, image: &Spanned<String>, desc: &TextureDesc, scale: f64) -> Result<Texture, SceneError> {
+        self.reject_options(desc, &["colors", "basis", "octaves", "seed"], "image")?;
+        self.reject(&desc.space, "image textures always use uv coordinates")?;
+        let (filter, wrap) = self.image_sampling(&desc.filter, &desc.wrap)?;
+        let path = self.base_dir.join(image.get_ref());
+        let texture = ImageTexture::load(&path, filter, wrap, scale)
+            .map_err(|err| self.error(image.span(), format!("failed to load image: {}", err)))?;
+        Ok(Texture::Image(texture))
+    }
+
+    fn normal_map(&self, desc: &NormalMapDesc) -> Result<Bump, SceneError> {
+        let scale = desc.scale.as_ref().map_or(1.0, |scale| *scale.get_ref());
+        if let Some(spanned) = &desc.scale {
+            self.check(spanned, scale > 0.0, "scale must be positive")?;
+        }
+        let strength = desc.strength.as_ref().map_or(1.0, |strength| *strength.get_ref());
+        if let Some(spanned) = &desc.strength {
+            self.check(spanned, strength >= 0.0, "strength must not be negative")?;
+        }
+        let (filter, wrap) = self.image_sampling(&desc.filter, &desc.wrap)?;
+        let path = self.base_dir.join(desc.image.get_ref());
+        let texture = ImageTexture::load_data(&path, filter, wrap, scale)
+            .map_err(|err| self.error(desc.image.span(), format!("failed to load image: {}", err)))?;
+        Ok(Bump::NormalMap { texture, strength })
+    }
+
+    /// The filter and wrap mode of an image, defaulting to bilinear and repeat.
+    fn image_sampling(
+        &self,
+        filter: &Option<Spanned<String>>,
+        wrap: &Option<Spanned<String>>,
+    ) -> Result<(TextureFilter, WrapMode), SceneError> {
+        let filter = match filter {
+            Some(name) => self.named(name, TextureFilter::from_name, "texture filter", "nearest or bilinear")?,
+            None => TextureFilter::Bilinear,
+        };
+        let wrap = match wrap {
+            Some(name) => self.named(name, WrapMode::from_name, "wrap mode", "repeat or clamp")?,
+            None => WrapMode::Repeat,
+        };
+        Ok((filter, wrap))
+    }
+
+    fn texture_colors(&self, desc: &TextureDesc) -> [Color; 2] {
+        desc.colors
+            .as_ref()
+            .map_or([[0, 0, 0], [255, 255, 255]], |colors| *colors.get_ref())
+            .map(color)
+    }
+
+    fn texture_space(&self, desc: &TextureDesc) -> Result<TextureSpace, SceneError> {
+        match &desc.space {
+            Some(name) => self.named(name, TextureSpace::from_name, "texture space", "world or uv"),
+            None => Ok(TextureSpace::World),
+        }
+    }
+
+    /// Fail if any of the texture options `names` was given for a texture of `kind`.
+    fn reject_options(&self, desc: &TextureDesc, names: &[&str], kind: &str) -> Result<(), SceneError> {
+        let spans = [
+            ("colors", desc.colors.as_ref().map(Spanned::span)),
+            ("basis", desc.basis.as_ref().map(Spanned::span)),
+            ("octaves", desc.octaves.as_ref().map(Spanned::span)),
+            ("seed", desc.seed.as_ref().map(Spanned::span)),
+            ("filter", desc.filter.as_ref().map(Spanned::span)),
+            ("wrap", desc.wrap.as_ref().map(Spanned::span)),
+        ];
+        for (name, span) in spans {
+            if let (true, Some(span)) = (names.contains(&name), span) {
+                return Err(self.error(span, format!("`{}` doesn't apply to {} textures", name, kind)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Look up a `name` with `from_name`, describing the `expected` names on failure.
+    fn named<T>(
+        &self,
+        name: &Spanned<String>,
+        from_name: impl Fn(&str) -> Option<T>,
+        what: &str,
+        expected: &str,
+    ) -> Result<T, SceneError> {
+        from_name(name.get_ref()).ok_or_else(|| {
+            self.error(
+                name.span(),
+                format!("unknown {} '{}', expected {}", what, name.get_ref(), expected),
+            )
+        })
+    }
+
+    fn sphere(&self, desc: &Spanned<SphereDesc>, materials: &BTreeMap<&str, Material>) -> Result<Sphere, SceneError> {
+        let desc = desc.get_ref();
+        self.check(&desc.radius, *desc.radius.get_ref() > 0.0, "radius must be positive")?;
+
+        Ok(Sphere {
+            center: vec3(desc.center),
+            radius: *desc.radius.get_ref(),
+            material: self.lookup_material(&desc.material, materials)?,
+        })
+    }
+
+    fn mesh(&self, desc: &MeshDesc, materials: &BTreeMap<&str, Material>) -> Result<Mesh, SceneError> {
+        let default_material = match &desc.material {
+            Some(name) => self.lookup_material(name, materials)?,
+            None => Material {
+                color: Color::from_srgb8(200, 200, 200),
+                ..Material::default()
+            },
+        };
+        let scale = desc.scale.as_ref().map(|scale| *scale.get_ref()).unwrap_or(1.0);
+        if let Some(spanned) = &desc.scale {
+            self.check(spanned, scale > 0.0, "scale must be positive")?;
+        }
+
+        let path = self.base_dir.join(desc.file.get_ref());
+        let mut mesh = obj::load(&path, default_material)
+            .map_err(|err| self.error(desc.file.span(), format!("failed to load mesh: {}", err)))?;
+        mesh.transform(&desc.position.map(vec3).unwrap_or(Vec3::new(0.0, 0.0, 0.0)), scale);
+        Ok(mesh)
+    }
+
+    fn lookup_material(&self, name: &Spanned<String>, materials: &BTreeMap<&str, Material>) -> Result<Material, SceneError> {
+        materials
+            .get(name.get_ref().as_str())
+            .cloned()
+            .ok_or_else(|| self.error(name.span(), format!("unknown material '{}'", name.get_ref())))
+    }
+
+    /// Read a direction vector, which must not be zero.
+    fn direction(&self, value: &Spanned<[f64; 3]>, what: &str) -> Result<Vec3, SceneError> {
+        let vector = vec3(*value.get_ref());
+        self.check(value, vector.length() > 0.0, &format!("{} must not be zero", what))?;
+        Ok(vector)
+    }
+
+    fn light(&self, desc: &Spanned<LightDesc>) -> Result<Light, SceneError> {
+        let span = desc.span();
+        let desc = desc.get_ref();
+        self.check(&desc.intensity, *desc.intensity.get_ref() >= 0.0, "intensity must not be negative")?;
+
+        let intensity = *desc.intensity.get_ref();
+        let color = desc.color.map(color).unwrap_or(Color::new(1.0, 1.0, 1.0));
+
+        if desc.kind.get_ref() != "point" {
+            self.reject(&desc.attenuation, "`attenuation` only applies to point lights")?;
+            self.reject(&desc.range, "`range` only applies to point lights")?;
+        }
+
+        match desc.kind.get_ref().as_str() {
+            "ambient" => Ok(Light::new_ambient(intensity, color)),
+            "point" => {
+                let position = desc
+                    .position
+                    .ok_or_else(|| self.error(span, "point light requires a `position`".to_string()))?;
+                let attenuation = match &desc.attenuation {
+                    Some(attenuation) => self.attenuation(attenuation)?,
+                    None => Attenuation::None,
+                };
+                if let Some(range) = &desc.range {
+                    self.check(range, *range.get_ref() > 0.0, "range must be positive")?;
+                }
+                let range = desc.range.as_ref().map(|range| *range.get_ref());
+                Ok(Light::new_attenuated_point(vec3(position), attenuation, range, intensity, color))
+            }
+            "directional" => {
+                let direction = desc
+                    .direction
+                    .as_ref()
+                    .ok_or_else(|| self.error(span, "directional light requires a `direction`".to_string()))?;
+                Ok(Light::new_directional(self.direction(direction, "direction")?, intensity, color))
+            }
+            "spot" => {
+                let (Some(position), Some(direction), Some(inner), Some(outer)) =
+                    (desc.position, &desc.direction, &desc.inner_angle, &desc.outer_angle)
+                else {
+                    return Err(self.error(
+                        span,
+                        "spot light requires a `position`, `direction`, `inner_angle` and `outer_angle`".to_string(),
+                    ));
+                };
+                let direction = self.direction(direction, "direction")?;
+                let (inner_angle, outer_angle) = (*inner.get_ref(), *outer.get_ref());
+                self.check(
+                    outer,
+                    outer_angle > 0.0 && outer_angle <= 180.0,
+                    "outer_angle must be between 0 and 180 degrees",
+                )?;
+                self.check(
+                    inner,
+                    (0.0..=outer_angle).contains(&inner_angle),
+                    "inner_angle must be between 0 and outer_angle",
+                )?;
+                Ok(Light::new_spot(vec3(position), direction, inner_angle, outer_angle, intensity, color))
+            }
+            "spherical" => {
+                let center = desc
+                    .center
+                    .ok_or_else(|| self.error(span.clone(), "spherical light requires a `center`".to_string()))?;
+                let radius = desc
+                    .radius
+                    .as_ref()
+                    .ok_or_else(|| self.error(span, "spherical light requires a `radius`".to_string()))?;
+                self.check(radius, *radius.get_ref() > 0.0, "radius must be positive")?;
+                let samples = self.light_samples(desc)?;
+                Ok(Light::new_spherical(vec3(center), *radius.get_ref(), samples, intensity, color))
+            }
+            "rectangular" => {
+                let (Some(corner), Some(u), Some(v)) = (desc.corner, desc.u, desc.v) else {
+                    return Err(self.error(
+                        span,
+                        "rectangular light requires a `corner` and edges `u` and `v`".to_string(),
+                    ));
+                };
+                let (u, v) = (vec3(u), vec3(v));
+                if u.cross(&v).length() == 0.0 {
+                    return Err(self.error(span, "light edges `u` and `v` must not be parallel".to_string()));
+                }
+                let samples = self.light_samples(desc)?;
+                Ok(Light::new_rectangular(vec3(corner), u, v, samples, intensity, color))
+            }
+            other => Err(self.error(
+                desc.kind.span(),
+                format!(
+                    "unknown light type '{}', expected ambient, point, directional, spot, spherical or rectangular",
+                    other
+                ),
+            )),
+        }
+    }
+
+    fn attenuation(&self, desc: &Spanned<AttenuationDesc>) -> Result<Attenuation, SceneError> {
+        match desc.get_ref() {
+            AttenuationDesc::Name(name) => match name.as_str() {
+                "none" => Ok(Attenuation::None),
+                "linear" => Ok(Attenuation::Linear),
+                "inverse_square" => Ok(Attenuation::InverseSquare),
+                _ => Err(self.error(
+                    desc.span(),
+                    format!(
+                        "unknown attenuation '{}', expected none, linear, inverse_square or a table of coefficients",
+                        name
+                    ),
+                )),
+            },
+            AttenuationDesc::Coefficients(coefficients) => {
+                let constant = coefficients.constant.unwrap_or(1.0);
+                let linear = coefficients.linear.unwrap_or(0.0);
+                let quadratic = coefficients.quadratic.unwrap_or(0.0);
+                let valid = [constant, linear, quadratic].iter().all(|&c| c >= 0.0) && constant + linear + quadratic > 0.0;
+                self.check(
+                    desc,
+                    valid,
+                    "attenuation coefficients must not be negative, and not all zero",
+                )?;
+                Ok(Attenuation::Coefficients {
+                    constant,
+                    linear,
+                    quadratic,
+                })
+            }
+        }
+    }
+
+    fn light_samples(&self, desc: &LightDesc) -> Result<u32, SceneError> {
+        match &desc.samples {
+            Some(samples) => {
+                self.check(samples, *samples.get_ref() > 0, "samples must be positive")?;
+                Ok(*samples.get_ref())
+            }
+            None => Ok(16),
+        }
+    }
+
+    fn check<T>(&self, value: &Spanned<T>, ok: bool, message: &str) -> Result<(), SceneError> {
+        if ok {
+            Ok(())
+        } else {
+            Err(self.error(value.span(), message.to_string()))
+        }
+    }
+
+    /// Fail if a key that doesn't apply was given.
+    fn reject<T>(&self, value: &Option<Spanned<T>>, message: &str) -> Result<(), SceneError> {
+        match value {
+            Some(value) => Err(self.error(value.span(), message.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    fn error(&self, span: Range<usize>, message: String) -> SceneError {
+        invalid(self.source, span.start, message)
+    }
+}
+
+fn invalid(source: &str, offset: usize, message: String) -> SceneError {
+    let (line, column) = line_column(source, offset);
+    SceneError::Invalid {
+        path: None,
+        line,
+        column,
+        message,
+    }
+}
+
+/// Convert a byte offset into a 1-based line and column.
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+fn vec3([x, y, z]: [f64; 3]) -> Vec3 {
+    Vec3::new(x, y, z)
+}
+
+fn color([r, g, b]: [u8; 3]) -> Color {
+    Color::from_srgb8(r, g, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(source: &str) -> (usize, usize, String) {
+        match parse(source) {
+            Err(SceneError::Invalid { line, column, message, .. }) => (line, column, message),
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("scene parsed"),
+        }
+    }
+
+    #[test]
+    fn reports_where_a_value_is_invalid() {
+        let source = "[[spheres]]\ncenter = [0.0, 0.0, 3.0]\nradius = 1.0\nmaterial = \"nope\"\n";
+        assert_eq!(location(source), (4, 12, "unknown material 'nope'".to_string()));
+    }
+
+    #[test]
+    fn reports_where_the_toml_is_malformed() {
+        let (line, column, _) = location("[camera]\nposition = [0.0, 0.0\n");
+        assert_eq!((line, column), (2, 21));
+    }
+
+    #[test]
+    fn rejects_attenuation_that_never_falls_off_or_grows() {
+        let light = |attenuation: &str| {
+            let light = "[[lights]]\ntype = \"point\"\nposition = [0.0, 0.0, 0.0]\nintensity = 1.0\n";
+            format!("{}attenuation = {}\n", light, attenuation)
+        };
+        let (line, column, message) = location(&light("{ constant = 0.0 }"));
+        assert_eq!((line, column), (5, 15));
+        assert_eq!(message, "attenuation coefficients must not be negative, and not all zero");
+        location(&light("{ constant = 0.0, linear = 0.0, quadratic = 0.0 }"));
+        location(&light("{ linear = -1.0 }"));
+        location(&light("\"cubic\""));
+        assert!(parse(&light("{ constant = 0.0, quadratic = 1.0 }")).is_ok());
+    }
+
+    #[test]
+    fn counts_columns_in_characters() {
+        let source = "# é\nab\n";
+        assert_eq!(line_column(source, 0), (1, 1));
+        assert_eq!(line_column(source, source.find('\n').unwrap()), (1, 4));
+        assert_eq!(line_column(source, source.find('b').unwrap()), (2, 2));
+        assert_eq!(line_column(source, source.len() + 10), (3, 1));
+    }
+
+    #[test]
+    fn displays_the_location() {
+        let err = SceneError::Invalid {
+            path: Some(PathBuf::from("scene.toml")),
+            line: 4,
+            column: 12,
+            message: "unknown material 'nope'".to_string(),
+        };
+        assert_eq!(err.to_string(), "scene.toml:4:12: unknown material 'nope'");
+    }
+}
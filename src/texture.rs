@@ -0,0 +1,460 @@
+//! Textures that vary a material's color over its surface.
+
+use crate::common::{Color, Vec3};
+use crate::noise::Noise;
+use crate::tonemap::TransferFunction;
+use image::{DynamicImage, ImageResult, Rgb32FImage};
+use std::f64::consts::PI;
+use std::path::Path;
+use std::sync::Arc;
+
+/// How far past a cell boundary a point may fall by rounding error, in pattern units.
+const CELL_EPSILON: f64 = 1e-6;
+
+/// Coordinates a texture is evaluated in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TextureSpace {
+    /// The world-space position of the hit, so patterns run through objects like a
+    /// solid block of material.
+    World,
+    /// The surface coordinates of the hit, see [`HitRecord::uv`](crate::HitRecord::uv).
+    Uv,
+}
+
+impl TextureSpace {
+    pub fn from_name(name: &str) -> Option<TextureSpace> {
+        match name {
+            "world" => Some(TextureSpace::World),
+            "uv" => Some(TextureSpace::Uv),
+            _ => None,
+        }
+    }
+}
+
+/// A procedural pattern, giving a value in `[0, 1]` for every point.
+///
+/// In world space the patterns are three dimensional, in UV space two dimensional with
+/// `(u, v)` taking the place of `(x, z)`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Pattern {
+    /// Alternating cubes (or squares) of size `1 / scale`.
+    Checker,
+    /// Stripes of width `1 / scale` across the X axis (or `u`).
+    Stripes,
+    /// Concentric rings of width `1 / scale` around the Y axis (or the middle of the UV
+    /// square).
+    Rings,
+    /// A ramp from 0 to 1 along the X axis (or `u`), repeating every `1 / scale`.
+    Gradient,
+}
+
+impl Pattern {
+    pub const ALL: [Pattern; 4] = [Pattern::Checker, Pattern::Stripes, Pattern::Rings, Pattern::Gradient];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Pattern::Checker => "checker",
+            Pattern::Stripes => "stripes",
+            Pattern::Rings => "rings",
+            Pattern::Gradient => "gradient",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Pattern> {
+        Pattern::ALL.into_iter().find(|pattern| pattern.name() == name)
+    }
+
+    /// Evaluate the pattern at an already scaled point.
+    fn value(&self, p: &Vec3) -> f64 {
+        // points on a cell boundary, like hits on an axis-aligned plane, land on either
+        // side by rounding error; the bias keeps them consistently in the upper cell
+        let cell = |x: f64| (x + CELL_EPSILON).floor();
+        let parity = |n: f64| (n as i64).rem_euclid(2) as f64;
+        match self {
+            Pattern::Checker => parity(cell(p.x) + cell(p.y) + cell(p.z)),
+            Pattern::Stripes => parity(cell(p.x)),
+            Pattern::Rings => parity(cell((p.x * p.x + p.z * p.z).sqrt())),
+            Pattern::Gradient => p.x.rem_euclid(1.0),
+        }
+    }
+}
+
+/// Varies a material's color over its surface.
+#[derive(Clone)]
+pub enum Texture {
+    Pattern(PatternTexture),
+    Image(ImageTexture),
+    Noise(NoiseTexture),
+}
+
+impl Texture {
+    /// The texture's color at a hit `point` with surface coordinates `uv`.
+    pub fn color(&self, point: &Vec3, uv: (f64, f64)) -> Color {
+        match self {
+            Texture::Pattern(texture) => texture.color(point, uv),
+            Texture::Image(texture) => texture.color(uv),
+            Texture::Noise(texture) => texture.color(point, uv),
+        }
+    }
+
+    /// The luminance of the texture's color, used when a texture drives a number rather
+    /// than a color. Black and white colors map pattern values straight through.
+    pub fn value(&self, point: &Vec3, uv: (f64, f64)) -> f64 {
+        self.color(point, uv).luminance()
+    }
+}
+
+/// A material property varied over the surface by a texture, see [`Texture::value`].
+#[derive(Clone)]
+pub struct TextureMap {
+    pub texture: Texture,
+    /// The property's values at texture values 0 and 1.
+    pub range: [f64; 2],
+}
+
+impl TextureMap {
+    pub fn new(texture: Texture, range: [f64; 2]) -> Self {
+        TextureMap { texture, range }
+    }
+
+    /// The property's value at a hit `point` with surface coordinates `uv`.
+    pub fn value(&self, point: &Vec3, uv: (f64, f64)) -> f64 {
+        let t = self.texture.value(point, uv);
+        self.range[0] + (self.range[1] - self.range[0]) * t
+    }
+}
+
+/// A procedural texture blending between two colors by a [`Pattern`].
+#[derive(Clone)]
+pub struct PatternTexture {
+    pub pattern: Pattern,
+    /// Colors at pattern values 0 and 1.
+    pub colors: [Color; 2],
+    /// Repetitions of the pattern per unit of distance.
+    pub scale: f64,
+    pub space: TextureSpace,
+}
+
+impl PatternTexture {
+    pub fn new(pattern: Pattern, colors: [Color; 2], scale: f64, space: TextureSpace) -> Self {
+        PatternTexture {
+            pattern,
+            colors,
+            scale,
+            space,
+        }
+    }
+
+    /// The texture's color at a hit `point` with surface coordinates `uv`.
+    pub fn color(&self, point: &Vec3, uv: (f64, f64)) -> Color {
+        let p = match self.space {
+            TextureSpace::World => *point,
+            // center the UV square on the origin, so rings are centered on the surface
+            TextureSpace::Uv if self.pattern == Pattern::Rings => Vec3::new(uv.0 - 0.5, 0.0, uv.1 - 0.5),
+            TextureSpace::Uv => Vec3::new(uv.0, 0.0, uv.1),
+        };
+        let t = self.pattern.value(&p.scale(self.scale));
+        self.colors[0].lerp(&self.colors[1], t)
+    }
+}
+
+/// The ways a [`NoiseTexture`] shapes its noise into a value in `[0, 1]`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NoisePattern {
+    /// Plain fractal noise.
+    Noise,
+    /// Fractal sum of the absolute noise, billowy with sharp creases.
+    Turbulence,
+    /// Veins across the X axis (or `u`), distorted by turbulence.
+    Marble,
+    /// Growth rings around the Y axis (or the middle of the UV square), distorted by noise.
+    Wood,
+    /// Soft, high contrast patches of fractal noise.
+    Clouds,
+}
+
+impl NoisePattern {
+    pub const ALL: [NoisePattern; 5] = [
+        NoisePattern::Noise,
+        NoisePattern::Turbulence,
+        NoisePattern::Marble,
+        NoisePattern::Wood,
+        NoisePattern::Clouds,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            NoisePattern::Noise => "noise",
+            NoisePattern::Turbulence => "turbulence",
+            NoisePattern::Marble => "marble",
+            NoisePattern::Wood => "wood",
+            NoisePattern::Clouds => "clouds",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<NoisePattern> {
+        NoisePattern::ALL.into_iter().find(|pattern| pattern.name() == name)
+    }
+}
+
+/// A texture blending between two colors by shaped, seeded gradient noise.
+#[derive(Clone)]
+pub struct NoiseTexture {
+    pub noise: Noise,
+    pub pattern: NoisePattern,
+    /// Colors at pattern values 0 and 1.
+    pub colors: [Color; 2],
+    /// Features per unit of distance, roughly.
+    pub scale: f64,
+    /// Layers of detail in the fractal sums.
+    pub octaves: u32,
+    pub space: TextureSpace,
+}
+
+impl NoiseTexture {
+    pub fn new(noise: Noise, pattern: NoisePattern, colors: [Color; 2], scale: f64, space: TextureSpace) -> Self {
+        NoiseTexture {
+            noise,
+            pattern,
+            colors,
+            scale,
+            octaves: 6,
+            space,
+        }
+    }
+
+    /// The texture's color at a hit `point` with surface coordinates `uv`.
+    pub fn color(&self, point: &Vec3, uv: (f64, f64)) -> Color {
+        let p = match self.space {
+            TextureSpace::World => *point,
+            TextureSpace::Uv => Vec3::new(uv.0 - 0.5, 0.0, uv.1 - 0.5),
+        };
+        let t = self.value(&p.scale(self.scale));
+        self.colors[0].lerp(&self.colors[1], t)
+    }
+
+    /// Evaluate the pattern at an already scaled point.
+    fn value(&self, p: &Vec3) -> f64 {
+        let t = match self.pattern {
+            NoisePattern::Noise => 0.5 + 0.5 * self.noise.fractal(p, self.octaves),
+            NoisePattern::Turbulence => self.noise.turbulence(p, self.octaves),
+            NoisePattern::Marble => {
+                let turbulence = self.noise.turbulence(p, self.octaves);
+                0.5 + 0.5 * ((p.x + 4.0 * turbulence) * PI).sin()
+            }
+            NoisePattern::Wood => {
+                // slow noise bends the rings; stretched along Y so the grain runs lengthwise
+                let q = Vec3::new(p.x * 0.3, p.y * 0.1, p.z * 0.3);
+                let distortion = self.noise.fractal(&q, self.octaves.min(3));
+                ((p.x * p.x + p.z * p.z).sqrt() + 2.0 * distortion).rem_euclid(1.0)
+            }
+            NoisePattern::Clouds => {
+                let t = 0.5 + self.noise.fractal(p, self.octaves);
+                // smoothstep for soft edges
+                let t = t.clamp(0.0, 1.0);
+                t * t * (3.0 - 2.0 * t)
+            }
+        };
+        t.clamp(0.0, 1.0)
+    }
+}
+
+/// How an image texture is sampled between texel centers.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TextureFilter {
+    /// The closest texel, giving a blocky look when magnified.
+    Nearest,
+    /// Linear interpolation between the four closest texels.
+    Bilinear,
+}
+
+impl TextureFilter {
+    pub const ALL: [TextureFilter; 2] = [TextureFilter::Nearest, TextureFilter::Bilinear];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TextureFilter::Nearest => "nearest",
+            TextureFilter::Bilinear => "bilinear",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<TextureFilter> {
+        TextureFilter::ALL.into_iter().find(|filter| filter.name() == name)
+    }
+}
+
+/// What an image texture shows outside the `[0, 1]` UV square.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WrapMode {
+    /// Tile the image.
+    Repeat,
+    /// Extend the edge texels.
+    Clamp,
+}
+
+impl WrapMode {
+    pub const ALL: [WrapMode; 2] = [WrapMode::Repeat, WrapMode::Clamp];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WrapMode::Repeat => "repeat",
+            WrapMode::Clamp => "clamp",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<WrapMode> {
+        WrapMode::ALL.into_iter().find(|wrap| wrap.name() == name)
+    }
+
+    /// Map a texel index onto `0..size`.
+    fn apply(&self, index: i64, size: u32) -> u32 {
+        match self {
+            WrapMode::Repeat => index.rem_euclid(size as i64) as u32,
+            WrapMode::Clamp => index.clamp(0, size as i64 - 1) as u32,
+        }
+    }
+}
+
+/// A texture mapping an image onto the surface coordinates of a hit, with `(0, 0)` at
+/// the bottom left corner of the image and `(1, 1)` at the top right.
+///
+/// The image is stored as linear colors and shared between clones.
+#[derive(Clone)]
+pub struct ImageTexture {
+    image: Arc<Rgb32FImage>,
+    pub filter: TextureFilter,
+    pub wrap: WrapMode,
+    /// Repetitions of the image per unit of `u` and `v`.
+    pub scale: f64,
+}
+
+impl ImageTexture {
+    /// A texture from an image of linear colors.
+    pub fn new(image: Rgb32FImage, filter: TextureFilter, wrap: WrapMode, scale: f64) -> Self {
+        ImageTexture {
+            image: Arc::new(image),
+            filter,
+            wrap,
+            scale,
+        }
+    }
+
+    /// Load a texture from an image file. Integer images are assumed to be sRGB encoded,
+    /// floating point images (e.g. Radiance HDR or OpenEXR) to be linear already.
+    pub fn load(path: impl AsRef<Path>, filter: TextureFilter, wrap: WrapMode, scale: f64) -> ImageResult<Self> {
+        let image = image::open(path)?;
+        let is_float = matches!(image, DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_));
+        let mut linear = image.into_rgb32f();
+        if !is_float {
+            for pixel in linear.pixels_mut() {
+                pixel.0 = pixel.0.map(|c| TransferFunction::Srgb.decode(c as f64) as f32);
+            }
+        }
+        Ok(ImageTexture::new(linear, filter, wrap, scale))
+    }
+
+    /// Load a texture of non-color data, e.g. a normal map, keeping the values as stored
+    /// (scaled to `[0, 1]` for integer images).
+    pub fn load_data(path: impl AsRef<Path>, filter: TextureFilter, wrap: WrapMode, scale: f64) -> ImageResult<Self> {
+        Ok(ImageTexture::new(image::open(path)?.into_rgb32f(), filter, wrap, scale))
+    }
+
+    pub fn width(&self) -> u32 {
+        self.image.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.image.height()
+    }
+
+    /// The texture's color at surface coordinates `uv`.
+    pub fn color(&self, uv: (f64, f64)) -> Color {
+        // continuous texel coordinates, with rows counted from the top of the image
+        let x = uv.0 * self.scale * self.width() as f64;
+        let y = (1.0 - uv.1 * self.scale) * self.height() as f64;
+        match self.filter {
+            TextureFilter::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            TextureFilter::Bilinear => {
+                // interpolate between the centers of the surrounding texels
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let top = self.texel(x0, y0).lerp(&self.texel(x0 + 1, y0), fx);
+                let bottom = self.texel(x0, y0 + 1).lerp(&self.texel(x0 + 1, y0 + 1), fx);
+                top.lerp(&bottom, fy)
+            }
+        }
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Color {
+        let [r, g, b] = self
+            .image
+            .get_pixel(self.wrap.apply(x, self.width()), self.wrap.apply(y, self.height()))
+            .0;
+        Color::new(r as f64, g as f64, b as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbImage;
+
+    /// A 2x2 texture, black and red on top, green and blue below, loaded through a file.
+    fn quad_texture(filter: TextureFilter, wrap: WrapMode) -> ImageTexture {
+        let texels = [[0, 0, 0], [255, 0, 0], [0, 255, 0], [0, 0, 255]].concat();
+        let path = std::env::temp_dir().join(format!("luxst-texture-{}-{:?}-{:?}.png", std::process::id(), filter, wrap));
+        RgbImage::from_raw(2, 2, texels).unwrap().save(&path).unwrap();
+        let texture = ImageTexture::load_data(&path, filter, wrap, 1.0).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        texture
+    }
+
+    fn assert_color(actual: Color, expected: [f64; 3]) {
+        let [r, g, b] = expected;
+        let close = (actual.r - r).abs() < 1e-6 && (actual.g - g).abs() < 1e-6 && (actual.b - b).abs() < 1e-6;
+        assert!(close, "({}, {}, {}) != {:?}", actual.r, actual.g, actual.b, expected);
+    }
+
+    #[test]
+    fn wrap_modes_map_indices_into_the_image() {
+        assert_eq!([-3, -1, 0, 1, 2, 5].map(|i| WrapMode::Repeat.apply(i, 2)), [1, 1, 0, 1, 0, 1]);
+        assert_eq!([-3, -1, 0, 1, 2, 5].map(|i| WrapMode::Clamp.apply(i, 2)), [0, 0, 0, 1, 1, 1]);
+    }
+
+    #[test]
+    fn image_textures_hit_texel_centers_exactly() {
+        let texture = quad_texture(TextureFilter::Bilinear, WrapMode::Repeat);
+        assert_color(texture.color((0.25, 0.75)), [0.0, 0.0, 0.0]);
+        assert_color(texture.color((0.75, 0.75)), [1.0, 0.0, 0.0]);
+        assert_color(texture.color((0.25, 0.25)), [0.0, 1.0, 0.0]);
+        assert_color(texture.color((0.75, 0.25)), [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn bilinear_filtering_blends_between_texel_centers() {
+        let texture = quad_texture(TextureFilter::Bilinear, WrapMode::Clamp);
+        assert_color(texture.color((0.5, 0.75)), [0.5, 0.0, 0.0]);
+        assert_color(texture.color((0.25, 0.5)), [0.0, 0.5, 0.0]);
+        assert_color(texture.color((0.5, 0.5)), [0.25, 0.25, 0.25]);
+
+        let nearest = quad_texture(TextureFilter::Nearest, WrapMode::Clamp);
+        assert_color(nearest.color((0.49, 0.51)), [0.0, 0.0, 0.0]);
+        assert_color(nearest.color((0.51, 0.49)), [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn wrapping_decides_what_lies_beyond_the_edges() {
+        // at the left edge, repeating blends with the opposite column, clamping doesn't
+        let repeat = quad_texture(TextureFilter::Bilinear, WrapMode::Repeat);
+        assert_color(repeat.color((0.0, 0.75)), [0.5, 0.0, 0.0]);
+        assert_color(repeat.color((1.25, 0.75)), [0.0, 0.0, 0.0]);
+        assert_color(repeat.color((-0.25, -0.75)), [0.0, 0.0, 1.0]);
+
+        let clamp = quad_texture(TextureFilter::Bilinear, WrapMode::Clamp);
+        assert_color(clamp.color((0.0, 0.75)), [0.0, 0.0, 0.0]);
+        assert_color(clamp.color((1.25, 0.75)), [1.0, 0.0, 0.0]);
+        assert_color(clamp.color((-3.0, -3.0)), [0.0, 1.0, 0.0]);
+    }
+}
@@ -0,0 +1,188 @@
+//! Tone mapping of linear HDR radiance to displayable 8-bit colors.
+//!
+//! Conversion happens in three steps: an exposure adjustment, a tone curve that brings
+//! radiance into `[0, 1]`, and a transfer function (sRGB by default) that encodes the
+//! linear result for display.
+
+use crate::common::Color;
+use image::{ImageBuffer, Rgb, Rgb32FImage, RgbImage};
+
+/// Curve that compresses linear radiance into the displayable `[0, 1]` range.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ToneMapOperator {
+    /// Cut off everything above 1, the behavior of the original 8-bit pipeline.
+    Clamp,
+    /// `x / (1 + x)`, which never fully saturates.
+    Reinhard,
+    /// Krzysztof Narkowicz's fit of the ACES filmic curve.
+    Aces,
+}
+
+impl ToneMapOperator {
+    pub const ALL: [ToneMapOperator; 3] = [ToneMapOperator::Clamp, ToneMapOperator::Reinhard, ToneMapOperator::Aces];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ToneMapOperator::Clamp => "clamp",
+            ToneMapOperator::Reinhard => "reinhard",
+            ToneMapOperator::Aces => "aces",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ToneMapOperator> {
+        ToneMapOperator::ALL.into_iter().find(|operator| operator.name() == name)
+    }
+
+    /// Map a single non-negative channel value into `[0, 1]`.
+    pub fn apply(&self, x: f64) -> f64 {
+        let x = x.max(0.0);
+        match self {
+            ToneMapOperator::Clamp => x.min(1.0),
+            ToneMapOperator::Reinhard => x / (1.0 + x),
+            ToneMapOperator::Aces => {
+                let (a, b, c, d, e) = (2.51, 0.03, 2.43, 0.59, 0.14);
+                (x * (a * x + b) / (x * (c * x + d) + e)).clamp(0.0, 1.0)
+            }
+        }
+    }
+}
+
+/// Encoding between linear values and the non-linear values stored in images.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TransferFunction {
+    /// The piecewise sRGB curve.
+    Srgb,
+    /// A plain power law, `encoded = linear^(1 / gamma)`. A gamma of 1 stores linear values.
+    Gamma(f64),
+}
+
+impl TransferFunction {
+    /// Encode a linear value in `[0, 1]`.
+    pub fn encode(&self, linear: f64) -> f64 {
+        match self {
+            TransferFunction::Srgb if linear <= 0.0031308 => linear * 12.92,
+            TransferFunction::Srgb => 1.055 * linear.powf(1.0 / 2.4) - 0.055,
+            TransferFunction::Gamma(gamma) => linear.powf(1.0 / gamma),
+        }
+    }
+
+    /// Decode an encoded value in `[0, 1]` into a linear value.
+    pub fn decode(&self, encoded: f64) -> f64 {
+        match self {
+            TransferFunction::Srgb if encoded <= 0.04045 => encoded / 12.92,
+            TransferFunction::Srgb => ((encoded + 0.055) / 1.055).powf(2.4),
+            TransferFunction::Gamma(gamma) => encoded.powf(*gamma),
+        }
+    }
+}
+
+/// Conversion from rendered radiance to display colors.
+#[derive(Clone, Copy, Debug)]
+pub struct ToneMapping {
+    pub operator: ToneMapOperator,
+    /// Exposure adjustment in stops: radiance is multiplied by `2^exposure` before the
+    /// operator is applied.
+    pub exposure: f64,
+    /// Encoding of the tone mapped values in the output image.
+    pub transfer: TransferFunction,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        ToneMapping {
+            operator: ToneMapOperator::Clamp,
+            exposure: 0.0,
+            transfer: TransferFunction::Srgb,
+        }
+    }
+}
+
+impl ToneMapping {
+    /// Expose, tone map and encode a color into display values in `[0, 1]`.
+    pub fn map(&self, color: &Color) -> Color {
+        let exposed = color.scale(self.exposure.exp2());
+        let channel = |c: f64| self.transfer.encode(self.operator.apply(c));
+        Color::new(channel(exposed.r), channel(exposed.g), channel(exposed.b))
+    }
+
+    /// Tone map a color and quantize it to 8 bits per channel.
+    pub fn to_rgb8(&self, color: &Color) -> [u8; 3] {
+        let mapped = self.map(color);
+        let channel = |c: f64| (c * 255.0).round() as u8;
+        [channel(mapped.r), channel(mapped.g), channel(mapped.b)]
+    }
+}
+
+/// Tone map a linear HDR image into an 8-bit image.
+pub fn tone_map(hdr: &Rgb32FImage, tone_mapping: &ToneMapping) -> RgbImage {
+    ImageBuffer::from_fn(hdr.width(), hdr.height(), |x, y| {
+        let [r, g, b] = hdr.get_pixel(x, y).0;
+        Rgb(tone_mapping.to_rgb8(&Color::new(r as f64, g as f64, b as f64)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operators_map_black_to_black_and_never_decrease() {
+        for operator in ToneMapOperator::ALL {
+            assert_eq!(operator.apply(0.0), 0.0, "{}", operator.name());
+            assert_eq!(operator.apply(-1.0), 0.0, "{}", operator.name());
+            let mut previous = 0.0;
+            for i in 1..=400 {
+                let mapped = operator.apply(i as f64 * 0.05);
+                assert!(mapped >= previous && mapped <= 1.0, "{} at {}", operator.name(), i as f64 * 0.05);
+                previous = mapped;
+            }
+        }
+    }
+
+    #[test]
+    fn clamp_saturates_at_one() {
+        assert_eq!(ToneMapOperator::Clamp.apply(0.5), 0.5);
+        assert_eq!(ToneMapOperator::Clamp.apply(1.0), 1.0);
+        assert_eq!(ToneMapOperator::Clamp.apply(20.0), 1.0);
+        // the other operators compress instead
+        assert!(ToneMapOperator::Reinhard.apply(20.0) < 1.0);
+        assert_eq!(ToneMapOperator::Reinhard.apply(1.0), 0.5);
+    }
+
+    #[test]
+    fn exposure_doubles_radiance_per_stop() {
+        let tone_mapping = ToneMapping {
+            exposure: 2.0,
+            transfer: TransferFunction::Gamma(1.0),
+            ..ToneMapping::default()
+        };
+        let mapped = tone_mapping.map(&Color::new(0.1, 0.2, 0.5));
+        assert!((mapped.r - 0.4).abs() < 1e-12 && (mapped.g - 0.8).abs() < 1e-12);
+        assert_eq!(mapped.b, 1.0);
+    }
+
+    #[test]
+    fn srgb_decoding_inverts_encoding() {
+        for i in 0..=1000 {
+            let x = i as f64 / 1000.0;
+            let srgb = TransferFunction::Srgb;
+            assert!((srgb.encode(srgb.decode(x)) - x).abs() < 1e-9, "{}", x);
+            assert!((srgb.decode(srgb.encode(x)) - x).abs() < 1e-9, "{}", x);
+        }
+        // the curve's linear segment and a mid-gray reference value
+        assert!((TransferFunction::Srgb.encode(0.001) - 0.01292).abs() < 1e-12);
+        assert!((TransferFunction::Srgb.decode(0.5) - 0.21404).abs() < 1e-5);
+    }
+
+    #[test]
+    fn gamma_is_a_power_law() {
+        let gamma = TransferFunction::Gamma(2.2);
+        assert!((gamma.encode(0.25) - 0.25f64.powf(1.0 / 2.2)).abs() < 1e-12);
+        assert!((gamma.decode(0.5) - 0.5f64.powf(2.2)).abs() < 1e-12);
+        assert!((gamma.encode(gamma.decode(0.3)) - 0.3).abs() < 1e-12);
+        assert_eq!(gamma.encode(0.0), 0.0);
+        assert_eq!(gamma.encode(1.0), 1.0);
+        // a gamma of 1 leaves values linear
+        assert_eq!(TransferFunction::Gamma(1.0).encode(0.3), 0.3);
+    }
+}